@@ -0,0 +1,19 @@
+use std::env;
+use std::process::Command;
+
+fn main() {
+    // Expose the rustc version and the target triple to `build_info!()`.
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        // e.g. "rustc 1.80.0 (051478957 2024-07-21)"
+        .and_then(|v| v.split_whitespace().nth(1).map(str::to_string))
+        .unwrap_or_else(|| "unknown".to_string());
+    let target = env::var("TARGET").unwrap_or_else(|_| "unknown".to_string());
+    println!("cargo:rustc-env=METRICS_PROCESS_RUSTC_VERSION={rustc_version}");
+    println!("cargo:rustc-env=METRICS_PROCESS_TARGET={target}");
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -13,6 +13,9 @@ fn main() {
 
     let collector = Collector::default();
     collector.describe();
+    let _handle = collector
+        .spawn(Duration::from_millis(750))
+        .expect("failed to spawn collector thread");
 
     let mut rng = thread_rng();
 
@@ -25,7 +28,6 @@ fn main() {
             fibonacci(n),
             s.elapsed().as_nanos()
         );
-        collector.collect();
         thread::sleep(Duration::from_millis(750));
     }
 }
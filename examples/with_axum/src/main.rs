@@ -1,5 +1,8 @@
+use std::time::Duration;
+
 use axum::{routing::get, Router};
 use metrics_exporter_prometheus::PrometheusBuilder;
+use metrics_process::tower::CollectLayer;
 use metrics_process::Collector;
 use tokio::net::TcpListener;
 
@@ -14,14 +17,18 @@ async fn main() {
     // Call `describe()` method to register help string.
     collector.describe();
 
-    let app = Router::new().route(
-        "/metrics",
-        get(move || {
-            // Collect information just before handle '/metrics'
-            collector.collect();
-            std::future::ready(handle.render())
-        }),
-    );
+    // Collect every COLLECT_INTERVAL seconds if given, or on each scrape otherwise.
+    let interval = std::env::var("COLLECT_INTERVAL")
+        .ok()
+        .map(|v| Duration::from_secs(v.parse().expect("invalid COLLECT_INTERVAL")));
+    let _periodic = interval.map(|interval| collector.run(interval).aligned(true).spawn());
+
+    let metrics = get(move || std::future::ready(handle.render()));
+    let app = match interval {
+        Some(_) => Router::new().route("/metrics", metrics),
+        // Collect information just before handle '/metrics'
+        None => Router::new().route("/metrics", metrics.layer(CollectLayer::new(collector))),
+    };
     let listener = TcpListener::bind("127.0.0.1:9000").await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
@@ -0,0 +1,161 @@
+//! Heap tracking global allocator.
+//!
+//! This module provides [`TrackingAllocator`], a [`GlobalAlloc`] that wraps another allocator
+//! (usually [`System`]) and counts the live heap bytes and the number of allocations and
+//! deallocations with relaxed atomics.
+//!
+//! Once it is registered as the global allocator, [`collect`] fills in
+//! `heap_bytes` of [`Metrics`] and `Collector` exports `process_heap_bytes` along with the
+//! allocation counters.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::alloc::System;
+//!
+//! use metrics_process::alloc::TrackingAllocator;
+//!
+//! #[global_allocator]
+//! static GLOBAL: TrackingAllocator<System> = TrackingAllocator::new(System);
+//!
+//! let v = vec![0u8; 1024];
+//! let stats = metrics_process::alloc::stats().unwrap();
+//! assert!(stats.heap_bytes >= 1024);
+//! # drop(v);
+//! ```
+//!
+//! [`collect`]: crate::collector::collect
+//! [`Metrics`]: crate::collector::Metrics
+//! [`System`]: std::alloc::System
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// A [`GlobalAlloc`] that counts heap usage and delegates the actual allocation to `A`.
+///
+/// The counters are shared by every instance, so only one instance should be registered as
+/// the global allocator.
+#[derive(Debug, Default)]
+pub struct TrackingAllocator<A> {
+    inner: A,
+}
+
+impl<A> TrackingAllocator<A> {
+    /// Create a new TrackingAllocator that wraps the provided allocator.
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for TrackingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        if !ptr.is_null() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        DEALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.inner.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            // A reallocation is neither a new allocation nor a deallocation, only the size of
+            // the live heap changes.
+            let old_size = layout.size();
+            if new_size > old_size {
+                ALLOCATED_BYTES.fetch_add((new_size - old_size) as u64, Ordering::Relaxed);
+            } else {
+                DEALLOCATED_BYTES.fetch_add((old_size - new_size) as u64, Ordering::Relaxed);
+            }
+        }
+        new_ptr
+    }
+}
+
+/// Heap usage counted by [`TrackingAllocator`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AllocStats {
+    /// Live heap size in bytes.
+    pub heap_bytes: u64,
+    /// Total number of allocations.
+    pub allocations_total: u64,
+    /// Total number of deallocations.
+    pub deallocations_total: u64,
+    /// Total allocated size in bytes.
+    pub allocated_bytes_total: u64,
+    /// Total deallocated size in bytes.
+    pub deallocated_bytes_total: u64,
+}
+
+/// Return the heap usage counted by [`TrackingAllocator`].
+///
+/// Returns `None` when no [`TrackingAllocator`] has allocated anything, which usually means that
+/// it is not registered as the global allocator.
+pub fn stats() -> Option<AllocStats> {
+    // Deallocations are loaded first so that the live heap size never goes negative.
+    let deallocations_total = DEALLOCATIONS.load(Ordering::Relaxed);
+    let deallocated_bytes_total = DEALLOCATED_BYTES.load(Ordering::Relaxed);
+    let allocations_total = ALLOCATIONS.load(Ordering::Relaxed);
+    let allocated_bytes_total = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    if allocations_total == 0 {
+        return None;
+    }
+    Some(AllocStats {
+        heap_bytes: allocated_bytes_total.saturating_sub(deallocated_bytes_total),
+        allocations_total,
+        deallocations_total,
+        allocated_bytes_total,
+        deallocated_bytes_total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::System;
+
+    #[test]
+    fn test_tracking_allocator_counts() {
+        let allocator = TrackingAllocator::new(System);
+        let layout = Layout::from_size_align(1024, 8).unwrap();
+        unsafe {
+            let ptr = allocator.alloc(layout);
+            let s = stats().unwrap();
+            assert!(s.allocations_total >= 1);
+            assert!(s.allocated_bytes_total >= 1024);
+            assert!(s.heap_bytes >= 1024);
+
+            let ptr = allocator.realloc(ptr, layout, 4096);
+            let t = stats().unwrap();
+            assert!(t.allocated_bytes_total >= s.allocated_bytes_total + 3072);
+            assert_eq!(t.allocations_total, s.allocations_total);
+
+            allocator.dealloc(ptr, Layout::from_size_align(4096, 8).unwrap());
+            let u = stats().unwrap();
+            assert!(u.deallocations_total >= 1);
+            assert!(u.deallocated_bytes_total >= 4096);
+        }
+    }
+}
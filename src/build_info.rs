@@ -0,0 +1,112 @@
+//! Build information of the application.
+//!
+//! [`BuildInfo`] carries the version, revision and build profile of the application along with
+//! the rustc version and target triple it was built with. Use the [`build_info!`] macro to fill
+//! it in at compile time and pass it to `CollectorBuilder::build_info` to export it as the
+//! `process_build_info` metric.
+//!
+//! [`build_info!`]: crate::build_info!
+
+/// Version of rustc used to build this crate, e.g. `1.80.0`.
+pub const RUSTC_VERSION: &str = env!("METRICS_PROCESS_RUSTC_VERSION");
+
+/// Target triple this crate was built for, e.g. `x86_64-unknown-linux-gnu`.
+pub const TARGET: &str = env!("METRICS_PROCESS_TARGET");
+
+/// Build information of the application.
+///
+/// Use the [`build_info!`] macro to create it at compile time.
+///
+/// [`build_info!`]: crate::build_info!
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// Version of the application crate (`CARGO_PKG_VERSION`).
+    pub version: &'static str,
+    /// User supplied revision of the application such as a git SHA.
+    pub revision: Option<&'static str>,
+    /// Build profile of the application, either `debug` or `release`.
+    pub profile: &'static str,
+    /// Version of rustc used to build the application.
+    pub rustc_version: &'static str,
+    /// Target triple the application was built for.
+    pub target: &'static str,
+}
+
+impl BuildInfo {
+    /// Iterate over `(label, value)` pairs of the `process_build_info` metric.
+    ///
+    /// The `revision` label is omitted when no revision is supplied.
+    pub fn labels(&self) -> impl Iterator<Item = (&'static str, &'static str)> {
+        IntoIterator::into_iter([
+            ("version", Some(self.version)),
+            ("revision", self.revision),
+            ("profile", Some(self.profile)),
+            ("rustc_version", Some(self.rustc_version)),
+            ("target", Some(self.target)),
+        ])
+        .filter_map(|(k, v)| v.map(|v| (k, v)))
+    }
+}
+
+/// Create a [`BuildInfo`] of the application at compile time.
+///
+/// The version and build profile are taken from the crate that invokes this macro. An optional
+/// revision (e.g. a git SHA) can be supplied as a `&'static str` expression.
+///
+/// # Examples
+///
+/// ```
+/// # use metrics_process::build_info;
+/// let info = build_info!();
+/// assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+///
+/// let info = build_info!("4f2a9c1");
+/// assert_eq!(info.revision, Some("4f2a9c1"));
+/// ```
+///
+/// Use `option_env!` to embed a revision provided by the build environment.
+///
+/// ```
+/// # use metrics_process::build_info;
+/// let mut info = build_info!();
+/// info.revision = option_env!("GIT_SHA");
+/// ```
+///
+/// [`BuildInfo`]: crate::build_info::BuildInfo
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::build_info::BuildInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            revision: None,
+            profile: if cfg!(debug_assertions) {
+                "debug"
+            } else {
+                "release"
+            },
+            rustc_version: $crate::build_info::RUSTC_VERSION,
+            target: $crate::build_info::TARGET,
+        }
+    };
+    ($revision:expr) => {
+        $crate::build_info::BuildInfo {
+            revision: Some($revision),
+            ..$crate::build_info!()
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_build_info() {
+        let info = build_info!("abc123");
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.revision, Some("abc123"));
+        assert!(matches!(info.profile, "debug" | "release"));
+        assert!(!info.rustc_version.is_empty());
+        assert!(!info.target.is_empty());
+        assert_eq!(info.labels().count(), 5);
+        assert_eq!(build_info!().labels().count(), 4);
+    }
+}
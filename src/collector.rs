@@ -4,13 +4,21 @@
 //! running process.  This can be useful to export these metrics via custom mechanisms
 //! rather than via the [metrics] crate.
 //!
-//! Use the [`collect`] function to create a snapshot of the current metrics.
+//! Use the [`collect`] function to create a snapshot of the current metrics, and
+//! [`Metrics::write_prometheus_text`] or [`Metrics::write_openmetrics_text`] to render it in the
+//! Prometheus text exposition format or the OpenMetrics text format.
 //!
 //! To export these metrics via the [metrics] crate however it is recommended to use the
 //! [`Collector`] struct.
 //!
 //! [`Collector`]: crate::Collector
 
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::ops;
+use std::sync::Arc;
+
 #[cfg_attr(target_os = "macos", path = "implementation/macos.rs")]
 #[cfg_attr(target_os = "linux", path = "implementation/linux.rs")]
 #[cfg_attr(target_os = "windows", path = "implementation/windows.rs")]
@@ -37,7 +45,107 @@ compile_error!(
 /// Creates a snapshot of the running process' [`Metrics`].
 ///
 /// Creates a new instance of [`Metrics`] with the current values of the running process.
-pub use implementation::collect;
+/// Metrics that could not be collected are `None`; use [`try_collect`] to find out why.
+pub fn collect() -> Metrics {
+    try_collect().0
+}
+
+/// Creates a snapshot of the running process' [`Metrics`] in the given [`MetricSet`].
+///
+/// The fields of the metrics that are not selected are always `None`.
+///
+/// # Examples
+///
+/// ```
+/// # use metrics_process::collector::{collect_selected, MetricSet};
+/// let metrics = collect_selected(MetricSet::CPU | MetricSet::MEMORY);
+/// assert_eq!(metrics.open_fds, None);
+/// ```
+pub fn collect_selected(set: MetricSet) -> Metrics {
+    try_collect_selected(set).0
+}
+
+/// Creates a snapshot of the running process' [`Metrics`] along with the reason of each
+/// metric that could not be collected.
+///
+/// Every field of the returned [`Metrics`] is either `Some` or has an [`Error`] in the returned
+/// [`Errors`].
+///
+/// # Examples
+///
+/// ```
+/// # use metrics_process::collector::{try_collect, Field};
+/// let (metrics, errors) = try_collect();
+/// if let Some(err) = errors.get(Field::OpenFds) {
+///     eprintln!("failed to collect open_fds: {err}");
+/// }
+/// ```
+pub fn try_collect() -> (Metrics, Errors) {
+    try_collect_selected(MetricSet::default())
+}
+
+/// Creates a snapshot of the running process' [`Metrics`] in the given [`MetricSet`] along with
+/// the reason of each selected metric that could not be collected.
+///
+/// Every selected field of the returned [`Metrics`] is either `Some` or has an [`Error`] in the
+/// returned [`Errors`]. Fields that are not selected are always `None` without an error.
+pub fn try_collect_selected(set: MetricSet) -> (Metrics, Errors) {
+    let (mut metrics, mut errors) = implementation::try_collect(set);
+    #[cfg(feature = "tracking-allocator")]
+    if set.contains(MetricSet::HEAP) {
+        metrics.heap_bytes = crate::alloc::stats().map(|s| s.heap_bytes);
+    }
+    // Backends never read the heap size, so whether it is missing is decided here alone.
+    errors.remove(Field::HeapBytes);
+    if metrics.heap_bytes.is_none() {
+        errors.insert(Field::HeapBytes, Error::Unsupported);
+    }
+    // Backends may read fields that share a source with a selected one (e.g. /proc/self/stat),
+    // so unselected fields are cleared here to keep the snapshot predictable.
+    for field in Field::ALL {
+        if !set.contains(field.metric_set()) {
+            metrics.clear(*field);
+        }
+    }
+    errors
+        .0
+        .retain(|(field, _)| set.contains(field.metric_set()));
+    (metrics, errors)
+}
+
+// Whether the field is ever collected on this platform, regardless of the selected MetricSet
+// or the kernel of the running system.
+#[cfg(feature = "metrics-rs")]
+pub(crate) fn is_supported(field: Field) -> bool {
+    match field {
+        Field::HeapBytes => cfg!(feature = "tracking-allocator"),
+        _ => !implementation::UNSUPPORTED_FIELDS.contains(&field),
+    }
+}
+
+/// Creates a snapshot of the running process' [`ThreadStates`].
+///
+/// Unlike [`collect`], this walks every thread of the running process (`/proc/self/task/*/stat`
+/// on Linux) so it is considerably more expensive. Returns `None` on non supported platforms.
+pub use implementation::collect_thread_states;
+
+/// Creates a snapshot of the running process' [`NumaMemory`].
+///
+/// Unlike [`collect`], this reads the whole memory map of the running process
+/// (`/proc/self/numa_maps` on Linux) so it is considerably more expensive. Returns `None` on non
+/// supported platforms or kernels built without NUMA support.
+pub use implementation::collect_numa_memory;
+
+/// Creates a snapshot of the running process' [`MallocStats`].
+///
+/// This calls `mallinfo2()` of glibc, which walks every malloc arena while holding its lock.
+/// Returns `None` on non supported platforms, non glibc targets or glibc prior to 2.33.
+pub use implementation::collect_malloc_stats;
+
+/// Creates a snapshot of the running process' [`ProcessInfo`].
+///
+/// Returns `None` on non supported platforms.
+pub use implementation::collect_process_info;
 
 /// Standard Prometheus process metrics.
 ///
@@ -46,10 +154,26 @@ pub use implementation::collect;
 ///
 /// To create a populated struct for the running process use the [`collect`] function.  The
 /// `Default` impl does not populate any metrics.
+///
+/// The struct is `#[non_exhaustive]` so that metrics can be added in minor releases; construct
+/// it with `Metrics::default()` and assign the fields instead of a struct expression.
+///
+/// With the `serde` feature, it implements `Serialize` and `Deserialize` with the field names as
+/// keys, which are kept stable across releases. Values are in the units noted in the names and
+/// docs of the fields (seconds, bytes or counts), and metrics that could not be collected are
+/// `null`. [`SchedPolicy`] is serialized as the `policy` label of `process_sched_info`, e.g.
+/// `"SCHED_OTHER"`, except that [`SchedPolicy::Unknown`] is serialized as `{"unknown": 7}` to
+/// keep the raw value.
 #[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct Metrics {
     /// Total user and system CPU time spent in seconds.
     pub cpu_seconds_total: Option<f64>,
+    /// Total user CPU time spent in seconds.
+    pub cpu_user_seconds_total: Option<f64>,
+    /// Total system CPU time spent in seconds.
+    pub cpu_system_seconds_total: Option<f64>,
     /// Number of open file descriptors.
     pub open_fds: Option<u64>,
     /// Maximum number of open file descriptors.
@@ -64,10 +188,594 @@ pub struct Metrics {
     pub virtual_memory_max_bytes: Option<u64>,
     /// Resident memory size in bytes.
     pub resident_memory_bytes: Option<u64>,
+    /// Process heap size in bytes.
+    ///
+    /// Only available when `alloc::TrackingAllocator` of the `tracking-allocator` feature is
+    /// registered as the global allocator.
+    pub heap_bytes: Option<u64>,
     /// Start time of the process since unix epoch in seconds.
     pub start_time_seconds: Option<u64>,
     /// Numberof OS threads in the process.
     pub threads: Option<u64>,
+    /// Total time spent waiting for block I/O in seconds.
+    ///
+    /// On Linux this is `delayacct_blkio_ticks` of `/proc/self/stat`, which the kernel only fills
+    /// in when delay accounting is enabled (`CONFIG_TASK_DELAY_ACCT` and, since Linux 5.14, the
+    /// `delayacct` boot parameter or `kernel.task_delayacct` sysctl). It stays 0 otherwise.
+    pub io_wait_seconds_total: Option<f64>,
+    /// Nice value of the process, from 19 (lowest priority) to -20 (highest priority).
+    pub nice: Option<i64>,
+    /// Kernel scheduling priority of the process.
+    ///
+    /// On Linux this is the negated real-time priority minus one for real-time policies and the
+    /// nice value plus 20 otherwise.
+    pub priority: Option<i64>,
+    /// Scheduling policy of the process.
+    pub sched_policy: Option<SchedPolicy>,
+    /// Real-time scheduling priority of the process, from 1 to 99.
+    ///
+    /// 0 indicates a non real-time scheduling policy.
+    pub rt_priority: Option<u64>,
+    /// Number of CPUs in the CPU affinity mask of the process.
+    pub cpu_affinity: Option<u64>,
+}
+
+impl Metrics {
+    fn clear(&mut self, field: Field) {
+        match field {
+            Field::CpuSecondsTotal => self.cpu_seconds_total = None,
+            Field::CpuUserSecondsTotal => self.cpu_user_seconds_total = None,
+            Field::CpuSystemSecondsTotal => self.cpu_system_seconds_total = None,
+            Field::OpenFds => self.open_fds = None,
+            Field::MaxFds => self.max_fds = None,
+            Field::VirtualMemoryBytes => self.virtual_memory_bytes = None,
+            Field::VirtualMemoryMaxBytes => self.virtual_memory_max_bytes = None,
+            Field::ResidentMemoryBytes => self.resident_memory_bytes = None,
+            Field::HeapBytes => self.heap_bytes = None,
+            Field::StartTimeSeconds => self.start_time_seconds = None,
+            Field::Threads => self.threads = None,
+            Field::IoWaitSecondsTotal => self.io_wait_seconds_total = None,
+            Field::Nice => self.nice = None,
+            Field::Priority => self.priority = None,
+            Field::SchedPolicy => self.sched_policy = None,
+            Field::RtPriority => self.rt_priority = None,
+            Field::CpuAffinity => self.cpu_affinity = None,
+        }
+    }
+}
+
+/// A set of metrics to collect.
+///
+/// Sets are combined with `|` and removed with `-`. The [`Default`] set contains every metric of
+/// [`Metrics`] but not the expensive [`THREAD_STATES`](Self::THREAD_STATES),
+/// [`NUMA_MEMORY`](Self::NUMA_MEMORY) and [`MALLOC_STATS`](Self::MALLOC_STATS), which have to be
+/// selected explicitly.
+///
+/// Sources of metrics that are not selected are never read, e.g. the file descriptor table is
+/// not walked unless [`FDS`](Self::FDS) is selected.
+///
+/// # Examples
+///
+/// ```
+/// # use metrics_process::collector::MetricSet;
+/// // Only CPU time and memory
+/// let set = MetricSet::CPU | MetricSet::MEMORY;
+/// // Everything but file descriptors, which are expensive to count with a huge fd table
+/// let set = MetricSet::default() - MetricSet::FDS;
+/// // Everything including thread states
+/// let set = MetricSet::default() | MetricSet::THREAD_STATES;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MetricSet(u32);
+
+impl MetricSet {
+    /// [`Metrics::cpu_seconds_total`], [`Metrics::cpu_user_seconds_total`] and
+    /// [`Metrics::cpu_system_seconds_total`]
+    pub const CPU: Self = Self(1 << 0);
+    /// [`Metrics::virtual_memory_bytes`], [`Metrics::virtual_memory_max_bytes`] and
+    /// [`Metrics::resident_memory_bytes`]
+    pub const MEMORY: Self = Self(1 << 1);
+    /// [`Metrics::heap_bytes`] along with the statistics of the tracking allocator and jemalloc
+    pub const HEAP: Self = Self(1 << 2);
+    /// [`Metrics::open_fds`] and [`Metrics::max_fds`]
+    pub const FDS: Self = Self(1 << 3);
+    /// [`Metrics::start_time_seconds`]
+    pub const START_TIME: Self = Self(1 << 4);
+    /// [`Metrics::threads`]
+    pub const THREADS: Self = Self(1 << 5);
+    /// [`Metrics::io_wait_seconds_total`]
+    pub const IO: Self = Self(1 << 6);
+    /// [`Metrics::nice`], [`Metrics::priority`], [`Metrics::sched_policy`],
+    /// [`Metrics::rt_priority`] and [`Metrics::cpu_affinity`]
+    pub const SCHED: Self = Self(1 << 7);
+    /// [`ThreadStates`], see [`collect_thread_states`]
+    pub const THREAD_STATES: Self = Self(1 << 8);
+    /// [`NumaMemory`], see [`collect_numa_memory`]
+    pub const NUMA_MEMORY: Self = Self(1 << 9);
+    /// [`MallocStats`], see [`collect_malloc_stats`]
+    pub const MALLOC_STATS: Self = Self(1 << 10);
+
+    /// Return an empty set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Return a set of all metrics, including the expensive ones.
+    pub const fn all() -> Self {
+        Self((1 << 11) - 1)
+    }
+
+    /// Return the union of the sets.
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Return `true` if all metrics in `other` are in the set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Return `true` if any metric in `other` is in the set.
+    pub const fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Return `true` if the set is empty.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl Default for MetricSet {
+    fn default() -> Self {
+        Self::all() - Self::THREAD_STATES - Self::NUMA_MEMORY - Self::MALLOC_STATS
+    }
+}
+
+impl ops::BitOr for MetricSet {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for MetricSet {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl ops::BitAnd for MetricSet {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl ops::Sub for MetricSet {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 & !rhs.0)
+    }
+}
+
+impl ops::SubAssign for MetricSet {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 &= !rhs.0;
+    }
+}
+
+/// Scheduling policy of a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SchedPolicy {
+    /// The standard round-robin time-sharing policy (`SCHED_OTHER`).
+    #[cfg_attr(feature = "serde", serde(rename = "SCHED_OTHER"))]
+    Other,
+    /// A first-in, first-out real-time policy (`SCHED_FIFO`).
+    #[cfg_attr(feature = "serde", serde(rename = "SCHED_FIFO"))]
+    Fifo,
+    /// A round-robin real-time policy (`SCHED_RR`).
+    #[cfg_attr(feature = "serde", serde(rename = "SCHED_RR"))]
+    RoundRobin,
+    /// A policy for "batch" style execution (`SCHED_BATCH`).
+    #[cfg_attr(feature = "serde", serde(rename = "SCHED_BATCH"))]
+    Batch,
+    /// A policy for running very low priority background jobs (`SCHED_IDLE`).
+    #[cfg_attr(feature = "serde", serde(rename = "SCHED_IDLE"))]
+    Idle,
+    /// A deadline based real-time policy (`SCHED_DEADLINE`).
+    #[cfg_attr(feature = "serde", serde(rename = "SCHED_DEADLINE"))]
+    Deadline,
+    /// An unknown policy with the raw value reported by the kernel.
+    #[cfg_attr(feature = "serde", serde(rename = "unknown"))]
+    Unknown(u32),
+}
+
+impl SchedPolicy {
+    /// Return the name of the policy, used as the `policy` label of `process_sched_info`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SchedPolicy::Other => "SCHED_OTHER",
+            SchedPolicy::Fifo => "SCHED_FIFO",
+            SchedPolicy::RoundRobin => "SCHED_RR",
+            SchedPolicy::Batch => "SCHED_BATCH",
+            SchedPolicy::Idle => "SCHED_IDLE",
+            SchedPolicy::Deadline => "SCHED_DEADLINE",
+            SchedPolicy::Unknown(_) => "unknown",
+        }
+    }
+}
+
+/// A field of [`Metrics`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Field {
+    /// [`Metrics::cpu_seconds_total`]
+    CpuSecondsTotal,
+    /// [`Metrics::cpu_user_seconds_total`]
+    CpuUserSecondsTotal,
+    /// [`Metrics::cpu_system_seconds_total`]
+    CpuSystemSecondsTotal,
+    /// [`Metrics::open_fds`]
+    OpenFds,
+    /// [`Metrics::max_fds`]
+    MaxFds,
+    /// [`Metrics::virtual_memory_bytes`]
+    VirtualMemoryBytes,
+    /// [`Metrics::virtual_memory_max_bytes`]
+    VirtualMemoryMaxBytes,
+    /// [`Metrics::resident_memory_bytes`]
+    ResidentMemoryBytes,
+    /// [`Metrics::heap_bytes`]
+    HeapBytes,
+    /// [`Metrics::start_time_seconds`]
+    StartTimeSeconds,
+    /// [`Metrics::threads`]
+    Threads,
+    /// [`Metrics::io_wait_seconds_total`]
+    IoWaitSecondsTotal,
+    /// [`Metrics::nice`]
+    Nice,
+    /// [`Metrics::priority`]
+    Priority,
+    /// [`Metrics::sched_policy`]
+    SchedPolicy,
+    /// [`Metrics::rt_priority`]
+    RtPriority,
+    /// [`Metrics::cpu_affinity`]
+    CpuAffinity,
+}
+
+impl Field {
+    /// All fields of [`Metrics`].
+    pub const ALL: &'static [Field] = &[
+        Field::CpuSecondsTotal,
+        Field::CpuUserSecondsTotal,
+        Field::CpuSystemSecondsTotal,
+        Field::OpenFds,
+        Field::MaxFds,
+        Field::VirtualMemoryBytes,
+        Field::VirtualMemoryMaxBytes,
+        Field::ResidentMemoryBytes,
+        Field::HeapBytes,
+        Field::StartTimeSeconds,
+        Field::Threads,
+        Field::IoWaitSecondsTotal,
+        Field::Nice,
+        Field::Priority,
+        Field::SchedPolicy,
+        Field::RtPriority,
+        Field::CpuAffinity,
+    ];
+
+    /// Return the [`MetricSet`] that selects the field.
+    pub fn metric_set(&self) -> MetricSet {
+        match self {
+            Field::CpuSecondsTotal | Field::CpuUserSecondsTotal | Field::CpuSystemSecondsTotal => {
+                MetricSet::CPU
+            }
+            Field::OpenFds | Field::MaxFds => MetricSet::FDS,
+            Field::VirtualMemoryBytes
+            | Field::VirtualMemoryMaxBytes
+            | Field::ResidentMemoryBytes => MetricSet::MEMORY,
+            Field::HeapBytes => MetricSet::HEAP,
+            Field::StartTimeSeconds => MetricSet::START_TIME,
+            Field::Threads => MetricSet::THREADS,
+            Field::IoWaitSecondsTotal => MetricSet::IO,
+            Field::Nice
+            | Field::Priority
+            | Field::SchedPolicy
+            | Field::RtPriority
+            | Field::CpuAffinity => MetricSet::SCHED,
+        }
+    }
+
+    /// Return the name of the field.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Field::CpuSecondsTotal => "cpu_seconds_total",
+            Field::CpuUserSecondsTotal => "cpu_user_seconds_total",
+            Field::CpuSystemSecondsTotal => "cpu_system_seconds_total",
+            Field::OpenFds => "open_fds",
+            Field::MaxFds => "max_fds",
+            Field::VirtualMemoryBytes => "virtual_memory_bytes",
+            Field::VirtualMemoryMaxBytes => "virtual_memory_max_bytes",
+            Field::ResidentMemoryBytes => "resident_memory_bytes",
+            Field::HeapBytes => "heap_bytes",
+            Field::StartTimeSeconds => "start_time_seconds",
+            Field::Threads => "threads",
+            Field::IoWaitSecondsTotal => "io_wait_seconds_total",
+            Field::Nice => "nice",
+            Field::Priority => "priority",
+            Field::SchedPolicy => "sched_policy",
+            Field::RtPriority => "rt_priority",
+            Field::CpuAffinity => "cpu_affinity",
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The reason why a metric could not be collected.
+///
+/// The underlying errors are reference counted because a single failure (e.g. reading
+/// `/proc/self/stat`) usually affects several metrics.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Error {
+    /// The metric is not supported on this platform or configuration.
+    Unsupported,
+    /// An I/O or OS error.
+    Io(Arc<io::Error>),
+    /// An error reading procfs.
+    #[cfg(target_os = "linux")]
+    Procfs(Arc<procfs::ProcError>),
+    /// An error reported by a platform API as a message.
+    Other(Arc<str>),
+}
+
+impl Error {
+    /// Return a short machine readable reason of the error.
+    ///
+    /// It is one of `unsupported`, `permission_denied`, `not_found`, `incomplete`, `io` or
+    /// `other`, and is used as the `reason` label of `process_collector_errors_total`.
+    pub fn reason(&self) -> &'static str {
+        fn io_reason(e: &io::Error) -> &'static str {
+            match e.kind() {
+                io::ErrorKind::PermissionDenied => "permission_denied",
+                io::ErrorKind::NotFound => "not_found",
+                _ => "io",
+            }
+        }
+        match self {
+            Error::Unsupported => "unsupported",
+            Error::Io(e) => io_reason(e),
+            #[cfg(target_os = "linux")]
+            Error::Procfs(e) => match e.as_ref() {
+                procfs::ProcError::PermissionDenied(_) => "permission_denied",
+                procfs::ProcError::NotFound(_) => "not_found",
+                procfs::ProcError::Incomplete(_) => "incomplete",
+                procfs::ProcError::Io(e, _) => io_reason(e),
+                _ => "other",
+            },
+            Error::Other(_) => "other",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Unsupported => f.write_str("not supported on this platform"),
+            Error::Io(e) => write!(f, "{e}"),
+            #[cfg(target_os = "linux")]
+            Error::Procfs(e) => write!(f, "{e}"),
+            Error::Other(e) => f.write_str(e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e.as_ref()),
+            #[cfg(target_os = "linux")]
+            Error::Procfs(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(Arc::new(e))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl From<procfs::ProcError> for Error {
+    fn from(e: procfs::ProcError) -> Self {
+        Error::Procfs(Arc::new(e))
+    }
+}
+
+/// Errors of the metrics that [`try_collect`] could not collect.
+#[derive(Debug, Default, Clone)]
+pub struct Errors(Vec<(Field, Error)>);
+
+impl Errors {
+    /// Return the error of the field, or `None` if the field was collected.
+    pub fn get(&self, field: Field) -> Option<&Error> {
+        self.0.iter().find(|(f, _)| *f == field).map(|(_, e)| e)
+    }
+
+    /// Iterate over the fields that could not be collected and their errors.
+    pub fn iter(&self) -> impl Iterator<Item = (Field, &Error)> {
+        self.0.iter().map(|(f, e)| (*f, e))
+    }
+
+    /// Return the number of fields that could not be collected.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Return `true` if all fields were collected.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn insert(&mut self, field: Field, error: Error) {
+        self.0.push((field, error));
+    }
+
+    pub(crate) fn remove(&mut self, field: Field) {
+        self.0.retain(|(f, _)| *f != field);
+    }
+
+    pub(crate) fn insert_all(&mut self, fields: &[Field], error: Error) {
+        for field in fields {
+            self.insert(*field, error.clone());
+        }
+    }
+}
+
+/// Number of threads of the running process in each scheduling state.
+///
+/// To create a populated struct for the running process use the [`collect_thread_states`]
+/// function.
+#[derive(Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThreadStates {
+    /// Number of running or runnable threads (`R`).
+    pub running: u64,
+    /// Number of threads in an interruptible sleep (`S`, `I`).
+    pub sleeping: u64,
+    /// Number of threads in an uninterruptible disk sleep (`D`).
+    ///
+    /// Threads stuck in this state are usually waiting on a hung storage or network filesystem.
+    pub disk_sleep: u64,
+    /// Number of stopped or traced threads (`T`, `t`).
+    pub stopped: u64,
+    /// Number of zombie or dead threads (`Z`, `X`).
+    pub zombie: u64,
+}
+
+impl ThreadStates {
+    /// Iterate over `(state, count)` pairs, where `state` is the value of the `state` label of
+    /// `process_threads_by_state`.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, u64)> {
+        IntoIterator::into_iter([
+            ("running", self.running),
+            ("sleeping", self.sleeping),
+            ("disk_sleep", self.disk_sleep),
+            ("stopped", self.stopped),
+            ("zombie", self.zombie),
+        ])
+    }
+}
+
+/// Resident memory of the running process on each NUMA node.
+///
+/// To create a populated struct for the running process use the [`collect_numa_memory`]
+/// function.
+#[derive(Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NumaMemory {
+    /// Resident memory size in bytes keyed by NUMA node.
+    pub resident_bytes: BTreeMap<u32, u64>,
+}
+
+/// Heap statistics of the glibc malloc implementation.
+///
+/// These are reported by the allocator itself, so they are available without replacing the
+/// global allocator. A large [`free_bytes`] while [`in_use_bytes`] is small indicates heap
+/// fragmentation, i.e. memory that has been freed but not returned to the operating system.
+///
+/// To create a populated struct for the running process use the [`collect_malloc_stats`]
+/// function.
+///
+/// [`free_bytes`]: MallocStats::free_bytes
+/// [`in_use_bytes`]: MallocStats::in_use_bytes
+#[derive(Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MallocStats {
+    /// Non-mmapped space allocated from the operating system in bytes (`arena`).
+    pub arena_bytes: u64,
+    /// Space in use by allocations in bytes (`uordblks`).
+    pub in_use_bytes: u64,
+    /// Free space held by the allocator in bytes (`fordblks`).
+    pub free_bytes: u64,
+    /// Space allocated in mmapped regions in bytes (`hblkhd`).
+    pub mmap_bytes: u64,
+}
+
+/// Identity of the running process.
+///
+/// To create a populated struct for the running process use the [`collect_process_info`]
+/// function.
+#[derive(Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessInfo {
+    /// Process ID.
+    pub pid: u64,
+    /// Parent process ID.
+    pub ppid: Option<u64>,
+    /// Basename of the executable.
+    pub exe: Option<String>,
+    /// Command line arguments, including the program.
+    ///
+    /// Note that it may contain secrets passed through the command line.
+    pub cmdline: Option<Vec<String>>,
+    /// Name (or ID if it cannot be resolved) of the effective user.
+    pub user: Option<String>,
+    /// Name (or ID if it cannot be resolved) of the effective group.
+    pub group: Option<String>,
+}
+
+impl ProcessInfo {
+    /// Return the command line formatted according to `cmdline`.
+    ///
+    /// Returns `None` when the command line is not available or [`Cmdline::Omit`] is given.
+    pub fn format_cmdline(&self, cmdline: Cmdline) -> Option<String> {
+        let args = self.cmdline.as_ref()?;
+        match cmdline {
+            Cmdline::Omit => None,
+            Cmdline::Program => args.first().cloned(),
+            Cmdline::Truncated(max_len) => {
+                let mut v = args.join(" ");
+                if v.len() > max_len {
+                    let mut end = max_len;
+                    while !v.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    v.truncate(end);
+                }
+                Some(v)
+            }
+        }
+    }
+}
+
+/// How much of the command line is exported by the `process_info` metric.
+///
+/// The command line may contain secrets passed through it, so it is omitted by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Cmdline {
+    /// Do not include the command line.
+    #[default]
+    Omit,
+    /// Include only the program (the first argument) of the command line.
+    Program,
+    /// Include the whole command line, truncated to the given number of bytes.
+    Truncated(usize),
 }
 
 #[cfg(test)]
@@ -95,6 +803,8 @@ mod tests {
         let m = collect();
         dbg!(&m);
         assert_matches!(m.cpu_seconds_total, Some(_));
+        assert_matches!(m.cpu_user_seconds_total, Some(_));
+        assert_matches!(m.cpu_system_seconds_total, Some(_));
         assert_matches!(m.open_fds, Some(_));
         assert_matches!(m.max_fds, Some(_));
         assert_matches!(m.virtual_memory_bytes, Some(_));
@@ -104,6 +814,226 @@ mod tests {
         assert_matches!(m.start_time_seconds, Some(_));
         #[cfg(not(target_os = "windows"))]
         assert_matches!(m.threads, Some(_));
+        #[cfg(target_os = "linux")]
+        {
+            assert_matches!(m.io_wait_seconds_total, Some(_));
+            assert_matches!(m.nice, Some(_));
+            assert_matches!(m.priority, Some(_));
+            assert_matches!(m.sched_policy, Some(_));
+            assert_matches!(m.rt_priority, Some(_));
+            assert_matches!(m.cpu_affinity, Some(v) if v >= 1);
+        }
+    }
+
+    #[test]
+    fn test_error_reason() {
+        assert_eq!(Error::Unsupported.reason(), "unsupported");
+        let e = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(Error::from(e).reason(), "permission_denied");
+        let e = io::Error::from(io::ErrorKind::NotFound);
+        assert_eq!(Error::from(e).reason(), "not_found");
+        let e = io::Error::from(io::ErrorKind::InvalidData);
+        assert_eq!(Error::from(e).reason(), "io");
+        assert_eq!(Error::Other("oops".into()).reason(), "other");
+    }
+
+    #[test]
+    fn test_try_collect_reports_every_missing_field() {
+        let (m, errors) = try_collect();
+        dbg!(&errors);
+        for field in Field::ALL {
+            let collected = match field {
+                Field::CpuSecondsTotal => m.cpu_seconds_total.is_some(),
+                Field::CpuUserSecondsTotal => m.cpu_user_seconds_total.is_some(),
+                Field::CpuSystemSecondsTotal => m.cpu_system_seconds_total.is_some(),
+                Field::OpenFds => m.open_fds.is_some(),
+                Field::MaxFds => m.max_fds.is_some(),
+                Field::VirtualMemoryBytes => m.virtual_memory_bytes.is_some(),
+                Field::VirtualMemoryMaxBytes => m.virtual_memory_max_bytes.is_some(),
+                Field::ResidentMemoryBytes => m.resident_memory_bytes.is_some(),
+                Field::HeapBytes => m.heap_bytes.is_some(),
+                Field::StartTimeSeconds => m.start_time_seconds.is_some(),
+                Field::Threads => m.threads.is_some(),
+                Field::IoWaitSecondsTotal => m.io_wait_seconds_total.is_some(),
+                Field::Nice => m.nice.is_some(),
+                Field::Priority => m.priority.is_some(),
+                Field::SchedPolicy => m.sched_policy.is_some(),
+                Field::RtPriority => m.rt_priority.is_some(),
+                Field::CpuAffinity => m.cpu_affinity.is_some(),
+            };
+            assert_ne!(collected, errors.get(*field).is_some(), "{field}");
+        }
+    }
+
+    #[cfg(feature = "tracking-allocator")]
+    #[test]
+    fn test_try_collect_heap_bytes_without_error() {
+        use std::alloc::{GlobalAlloc, Layout, System};
+
+        let allocator = crate::alloc::TrackingAllocator::new(System);
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        // SAFETY: the layout has a non-zero size and the pointer is deallocated with it.
+        unsafe { allocator.dealloc(allocator.alloc(layout), layout) };
+        let (m, errors) = try_collect_selected(MetricSet::HEAP);
+        dbg!(&m, &errors);
+        assert_matches!(m.heap_bytes, Some(_));
+        assert_matches!(errors.get(Field::HeapBytes), None);
+    }
+
+    #[test]
+    fn test_try_collect_selected_skips_unselected_fields() {
+        let set = MetricSet::CPU | MetricSet::MEMORY;
+        let (m, errors) = try_collect_selected(set);
+        dbg!(&m, &errors);
+        assert_matches!(m.open_fds, None);
+        assert_matches!(m.max_fds, None);
+        assert_matches!(m.start_time_seconds, None);
+        assert_matches!(m.threads, None);
+        assert_matches!(m.heap_bytes, None);
+        assert_matches!(m.nice, None);
+        assert_matches!(m.cpu_affinity, None);
+        assert!(errors
+            .iter()
+            .all(|(field, _)| set.contains(field.metric_set())));
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "linux",
+            target_os = "windows",
+            target_os = "freebsd"
+        ))]
+        {
+            assert_matches!(m.cpu_seconds_total, Some(_));
+            assert_matches!(m.resident_memory_bytes, Some(_));
+        }
+    }
+
+    #[test]
+    fn test_metric_set() {
+        let set = MetricSet::CPU | MetricSet::MEMORY;
+        assert!(set.contains(MetricSet::CPU));
+        assert!(!set.contains(MetricSet::CPU | MetricSet::FDS));
+        assert!(set.intersects(MetricSet::CPU | MetricSet::FDS));
+        assert_eq!(set - MetricSet::CPU, MetricSet::MEMORY);
+        assert!(MetricSet::empty().is_empty());
+        assert!(!MetricSet::default().contains(MetricSet::THREAD_STATES));
+        for field in Field::ALL {
+            assert!(
+                MetricSet::default().contains(field.metric_set()),
+                "{}",
+                field
+            );
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_collect_thread_states_ok() {
+        let s = collect_thread_states().expect("thread states must be available on Linux");
+        dbg!(&s);
+        // The thread calling collect_thread_states() is running
+        assert!(s.running >= 1);
+        assert!(s.iter().map(|(_, v)| v).sum::<u64>() >= 1);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_collect_numa_memory_ok() {
+        // numa_maps does not exist on kernels built without CONFIG_NUMA
+        if !std::path::Path::new("/proc/self/numa_maps").exists() {
+            return;
+        }
+        let n = collect_numa_memory().expect("numa_maps must be readable");
+        dbg!(&n);
+        assert!(n.resident_bytes.values().sum::<u64>() > 0);
+    }
+
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    #[test]
+    fn test_collect_malloc_stats_ok() {
+        let v = vec![0u8; 1024];
+        let s = collect_malloc_stats().expect("mallinfo2 must be available on glibc 2.33+");
+        dbg!(&s);
+        assert!(s.arena_bytes > 0);
+        assert!(s.in_use_bytes > 0);
+        drop(v);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_collect_process_info_ok() {
+        let i = collect_process_info().expect("process info must be available on Linux");
+        dbg!(&i);
+        assert_eq!(i.pid, std::process::id() as u64);
+        assert_matches!(i.ppid, Some(_));
+        assert_matches!(i.exe, Some(_));
+        assert_matches!(i.cmdline, Some(_));
+        assert_matches!(i.user, Some(_));
+        assert_matches!(i.group, Some(_));
+    }
+
+    #[test]
+    fn test_process_info_format_cmdline() {
+        let i = ProcessInfo {
+            cmdline: Some(vec![
+                "/usr/bin/app".to_string(),
+                "--password".to_string(),
+                "sécret".to_string(),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(i.format_cmdline(Cmdline::Omit), None);
+        assert_eq!(
+            i.format_cmdline(Cmdline::Program),
+            Some("/usr/bin/app".to_string())
+        );
+        assert_eq!(
+            i.format_cmdline(Cmdline::Truncated(12)),
+            Some("/usr/bin/app".to_string())
+        );
+        // Truncation never splits a multi-byte character
+        assert_eq!(
+            i.format_cmdline(Cmdline::Truncated(25)),
+            Some("/usr/bin/app --password s".to_string())
+        );
+        assert_eq!(
+            i.format_cmdline(Cmdline::Truncated(26)),
+            Some("/usr/bin/app --password s".to_string())
+        );
+        assert_eq!(
+            i.format_cmdline(Cmdline::Truncated(1024)),
+            Some("/usr/bin/app --password sécret".to_string())
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_metrics_serde() {
+        let m = Metrics {
+            cpu_seconds_total: Some(1.5),
+            resident_memory_bytes: Some(4096),
+            sched_policy: Some(SchedPolicy::Other),
+            ..Default::default()
+        };
+        let v = serde_json::to_value(&m).unwrap();
+        assert_eq!(v["cpu_seconds_total"], 1.5);
+        assert_eq!(v["resident_memory_bytes"], 4096);
+        assert_eq!(v["open_fds"], serde_json::Value::Null);
+        assert_eq!(v["sched_policy"], "SCHED_OTHER");
+        assert_eq!(serde_json::from_value::<Metrics>(v).unwrap(), m);
+
+        let json = serde_json::to_string(&SchedPolicy::Unknown(7)).unwrap();
+        assert_eq!(json, r#"{"unknown":7}"#);
+        assert_eq!(
+            serde_json::from_str::<SchedPolicy>(&json).unwrap(),
+            SchedPolicy::Unknown(7)
+        );
+
+        let n = NumaMemory {
+            resident_bytes: IntoIterator::into_iter([(0, 4096), (1, 8192)]).collect(),
+        };
+        let json = serde_json::to_string(&n).unwrap();
+        assert_eq!(json, r#"{"resident_bytes":{"0":4096,"1":8192}}"#);
+        assert_eq!(serde_json::from_str::<NumaMemory>(&json).unwrap(), n);
     }
 
     #[cfg(target_os = "openbsd")]
@@ -115,6 +1045,8 @@ mod tests {
         let m = collect();
         dbg!(&m);
         assert_matches!(m.cpu_seconds_total, Some(_));
+        assert_matches!(m.cpu_user_seconds_total, Some(_));
+        assert_matches!(m.cpu_system_seconds_total, Some(_));
         assert_matches!(m.open_fds, None);
         assert_matches!(m.max_fds, Some(_));
         assert_matches!(m.virtual_memory_bytes, None);
@@ -122,6 +1054,12 @@ mod tests {
         assert_matches!(m.resident_memory_bytes, Some(_));
         assert_matches!(m.start_time_seconds, Some(_));
         assert_matches!(m.threads, None);
+        assert_matches!(m.io_wait_seconds_total, None);
+        assert_matches!(m.nice, None);
+        assert_matches!(m.priority, None);
+        assert_matches!(m.sched_policy, None);
+        assert_matches!(m.rt_priority, None);
+        assert_matches!(m.cpu_affinity, None);
     }
 
     #[cfg(not(target_os = "macos"))]
@@ -136,12 +1074,25 @@ mod tests {
         let m = collect();
         dbg!(&m);
         assert_matches!(m.cpu_seconds_total, None);
+        assert_matches!(m.cpu_user_seconds_total, None);
+        assert_matches!(m.cpu_system_seconds_total, None);
         assert_matches!(m.open_fds, None);
         assert_matches!(m.max_fds, None);
         assert_matches!(m.virtual_memory_bytes, None);
         assert_matches!(m.virtual_memory_max_bytes, None);
         assert_matches!(m.resident_memory_bytes, None);
+        assert_matches!(m.heap_bytes, None);
         assert_matches!(m.start_time_seconds, None);
         assert_matches!(m.threads, None);
+        assert_matches!(m.io_wait_seconds_total, None);
+        assert_matches!(m.nice, None);
+        assert_matches!(m.priority, None);
+        assert_matches!(m.sched_policy, None);
+        assert_matches!(m.rt_priority, None);
+        assert_matches!(m.cpu_affinity, None);
+        assert_matches!(collect_thread_states(), None);
+        assert_matches!(collect_numa_memory(), None);
+        assert_matches!(collect_malloc_stats(), None);
+        assert_matches!(collect_process_info(), None);
     }
 }
@@ -0,0 +1,643 @@
+// Names and help strings of the metrics of `collector::Metrics`, shared by the text renderers,
+// the integrations of Prometheus client crates and `Collector` so that all of them agree.
+
+use std::io;
+
+use crate::collector::{Field, MetricSet, Metrics};
+
+pub(crate) enum Kind {
+    Counter,
+    Gauge,
+}
+
+pub(crate) struct Definition {
+    #[cfg_attr(not(feature = "metrics-rs"), allow(dead_code))]
+    pub(crate) field: Field,
+    pub(crate) name: &'static str,
+    // Name in the OpenTelemetry semantic conventions, used by `NamingScheme::OpenTelemetry`
+    #[cfg_attr(not(feature = "metrics-rs"), allow(dead_code))]
+    pub(crate) otel_name: &'static str,
+    pub(crate) help: &'static str,
+    // Help string with `NamingScheme::OpenTelemetry` when it differs from `help`
+    #[cfg_attr(not(feature = "metrics-rs"), allow(dead_code))]
+    pub(crate) otel_help: Option<&'static str>,
+    pub(crate) unit: Option<&'static str>,
+    pub(crate) kind: Kind,
+    #[cfg_attr(
+        not(any(
+            feature = "metrics-rs",
+            feature = "prometheus",
+            feature = "prometheus-client"
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) set: MetricSet,
+    pub(crate) value: fn(&Metrics) -> Option<f64>,
+}
+
+impl Definition {
+    // Name of the metric without the `_total` suffix of counters, which is the name of the
+    // metric family in OpenMetrics.
+    pub(crate) fn family_name(&self) -> &'static str {
+        match self.kind {
+            Kind::Counter => self.name.strip_suffix("_total").unwrap_or(self.name),
+            Kind::Gauge => self.name,
+        }
+    }
+}
+
+pub(crate) const DEFINITIONS: &[Definition] = &[
+    Definition {
+        field: Field::CpuSecondsTotal,
+        name: "process_cpu_seconds_total",
+        otel_name: "process.cpu.time",
+        help: "Total user and system CPU time spent in seconds.",
+        otel_help: Some("Total CPU time spent in seconds by CPU mode."),
+        unit: Some("seconds"),
+        kind: Kind::Counter,
+        set: MetricSet::CPU,
+        value: |m| m.cpu_seconds_total,
+    },
+    Definition {
+        field: Field::OpenFds,
+        name: "process_open_fds",
+        otel_name: "process.open_file_descriptor.count",
+        help: "Number of open file descriptors.",
+        otel_help: None,
+        unit: None,
+        kind: Kind::Gauge,
+        set: MetricSet::FDS,
+        value: |m| m.open_fds.map(|v| v as f64),
+    },
+    Definition {
+        field: Field::MaxFds,
+        name: "process_max_fds",
+        otel_name: "process.open_file_descriptor.limit",
+        help: "Maximum number of open file descriptors.",
+        otel_help: None,
+        unit: None,
+        kind: Kind::Gauge,
+        set: MetricSet::FDS,
+        value: |m| m.max_fds.map(|v| v as f64),
+    },
+    Definition {
+        field: Field::VirtualMemoryBytes,
+        name: "process_virtual_memory_bytes",
+        otel_name: "process.memory.virtual",
+        help: "Virtual memory size in bytes.",
+        otel_help: None,
+        unit: Some("bytes"),
+        kind: Kind::Gauge,
+        set: MetricSet::MEMORY,
+        value: |m| m.virtual_memory_bytes.map(|v| v as f64),
+    },
+    Definition {
+        field: Field::VirtualMemoryMaxBytes,
+        name: "process_virtual_memory_max_bytes",
+        otel_name: "process.memory.virtual.limit",
+        help: "Maximum amount of virtual memory available in bytes.",
+        otel_help: None,
+        unit: Some("bytes"),
+        kind: Kind::Gauge,
+        set: MetricSet::MEMORY,
+        value: |m| m.virtual_memory_max_bytes.map(|v| v as f64),
+    },
+    Definition {
+        field: Field::ResidentMemoryBytes,
+        name: "process_resident_memory_bytes",
+        otel_name: "process.memory.usage",
+        help: "Resident memory size in bytes.",
+        otel_help: None,
+        unit: Some("bytes"),
+        kind: Kind::Gauge,
+        set: MetricSet::MEMORY,
+        value: |m| m.resident_memory_bytes.map(|v| v as f64),
+    },
+    Definition {
+        field: Field::HeapBytes,
+        name: "process_heap_bytes",
+        otel_name: "process.heap.usage",
+        help: "Process heap size in bytes.",
+        otel_help: None,
+        unit: Some("bytes"),
+        kind: Kind::Gauge,
+        set: MetricSet::HEAP,
+        value: |m| m.heap_bytes.map(|v| v as f64),
+    },
+    Definition {
+        field: Field::StartTimeSeconds,
+        name: "process_start_time_seconds",
+        otel_name: "process.start_time",
+        help: "Start time of the process since unix epoch in seconds.",
+        otel_help: None,
+        unit: Some("seconds"),
+        kind: Kind::Gauge,
+        set: MetricSet::START_TIME,
+        value: |m| m.start_time_seconds.map(|v| v as f64),
+    },
+    Definition {
+        field: Field::Threads,
+        name: "process_threads",
+        otel_name: "process.thread.count",
+        help: "Number of OS threads in the process.",
+        otel_help: None,
+        unit: None,
+        kind: Kind::Gauge,
+        set: MetricSet::THREADS,
+        value: |m| m.threads.map(|v| v as f64),
+    },
+    Definition {
+        field: Field::IoWaitSecondsTotal,
+        name: "process_io_wait_seconds_total",
+        otel_name: "process.io.wait_time",
+        help: "Total time spent waiting for block I/O in seconds.",
+        otel_help: None,
+        unit: Some("seconds"),
+        kind: Kind::Counter,
+        set: MetricSet::IO,
+        value: |m| m.io_wait_seconds_total,
+    },
+    Definition {
+        field: Field::Nice,
+        name: "process_nice",
+        otel_name: "process.nice",
+        help: "Nice value of the process.",
+        otel_help: None,
+        unit: None,
+        kind: Kind::Gauge,
+        set: MetricSet::SCHED,
+        value: |m| m.nice.map(|v| v as f64),
+    },
+    Definition {
+        field: Field::Priority,
+        name: "process_priority",
+        otel_name: "process.priority",
+        help: "Kernel scheduling priority of the process.",
+        otel_help: None,
+        unit: None,
+        kind: Kind::Gauge,
+        set: MetricSet::SCHED,
+        value: |m| m.priority.map(|v| v as f64),
+    },
+    Definition {
+        field: Field::RtPriority,
+        name: "process_rt_priority",
+        otel_name: "process.rt_priority",
+        help: "Real-time scheduling priority of the process.",
+        otel_help: None,
+        unit: None,
+        kind: Kind::Gauge,
+        set: MetricSet::SCHED,
+        value: |m| m.rt_priority.map(|v| v as f64),
+    },
+    Definition {
+        field: Field::CpuAffinity,
+        name: "process_cpu_affinity",
+        otel_name: "process.cpu.affinity",
+        help: "Number of CPUs in the CPU affinity mask of the process.",
+        otel_help: None,
+        unit: None,
+        kind: Kind::Gauge,
+        set: MetricSet::SCHED,
+        value: |m| m.cpu_affinity.map(|v| v as f64),
+    },
+];
+
+pub(crate) const SCHED_INFO_NAME: &str = "process_sched_info";
+// Name of the OpenMetrics info family of `SCHED_INFO_NAME`, which is the name without `_info`
+pub(crate) const SCHED_INFO_FAMILY: &str = "process_sched";
+#[cfg(feature = "metrics-rs")]
+pub(crate) const SCHED_INFO_OTEL_NAME: &str = "process.sched.info";
+pub(crate) const SCHED_INFO_HELP: &str = "Scheduling policy of the process, labeled by policy.";
+
+impl Metrics {
+    /// Write the metrics in the [Prometheus text exposition format] with the provided prefix
+    /// that is prepended to metric names.
+    ///
+    /// The names and help strings are the same as those of `Collector::describe()` with the
+    /// default naming scheme.
+    /// Metrics that are `None` are omitted from the output. This does not depend on any crate,
+    /// so it is available without the default features.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use metrics_process::collector::collect;
+    /// let mut buf = Vec::new();
+    /// collect().write_prometheus_text(&mut buf, "")?;
+    /// let text = String::from_utf8(buf).unwrap();
+    /// assert!(text.contains("# TYPE process_resident_memory_bytes gauge\n"));
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    /// [Prometheus text exposition format]: https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format
+    pub fn write_prometheus_text(
+        &self,
+        w: &mut impl io::Write,
+        prefix: impl AsRef<str>,
+    ) -> io::Result<()> {
+        let prefix = prefix.as_ref();
+        for def in DEFINITIONS {
+            let v = match (def.value)(self) {
+                Some(v) => v,
+                None => continue,
+            };
+            let kind = match def.kind {
+                Kind::Counter => "counter",
+                Kind::Gauge => "gauge",
+            };
+            writeln!(w, "# HELP {prefix}{} {}", def.name, def.help)?;
+            writeln!(w, "# TYPE {prefix}{} {kind}", def.name)?;
+            writeln!(w, "{prefix}{} {}", def.name, FloatValue(v))?;
+        }
+        if let Some(policy) = self.sched_policy {
+            writeln!(w, "# HELP {prefix}{SCHED_INFO_NAME} {SCHED_INFO_HELP}")?;
+            writeln!(w, "# TYPE {prefix}{SCHED_INFO_NAME} gauge")?;
+            writeln!(
+                w,
+                "{prefix}{SCHED_INFO_NAME}{{policy=\"{}\"}} 1",
+                policy.as_str()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write the metrics in the [OpenMetrics text format] with the provided prefix that is
+    /// prepended to metric names.
+    ///
+    /// The sample names and help strings are the same as those of
+    /// [`write_prometheus_text()`](Self::write_prometheus_text), but counters are described by
+    /// the name without the `_total` suffix, metrics measured in seconds or bytes have a
+    /// `# UNIT` line, `process_sched_info` is an info metric, and the output is terminated by
+    /// `# EOF`. Counters also have a `_created` sample of the start time of the process when
+    /// `start_time_seconds` is available, since they count from zero at that time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use metrics_process::collector::collect;
+    /// let mut buf = Vec::new();
+    /// collect().write_openmetrics_text(&mut buf, "")?;
+    /// let text = String::from_utf8(buf).unwrap();
+    /// assert!(text.ends_with("# EOF\n"));
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    /// [OpenMetrics text format]: https://github.com/prometheus/OpenMetrics/blob/main/specification/OpenMetrics.md
+    pub fn write_openmetrics_text(
+        &self,
+        w: &mut impl io::Write,
+        prefix: impl AsRef<str>,
+    ) -> io::Result<()> {
+        let prefix = prefix.as_ref();
+        for def in DEFINITIONS {
+            let v = match (def.value)(self) {
+                Some(v) => v,
+                None => continue,
+            };
+            let family = def.family_name();
+            let kind = match def.kind {
+                Kind::Counter => "counter",
+                Kind::Gauge => "gauge",
+            };
+            writeln!(w, "# HELP {prefix}{family} {}", def.help)?;
+            writeln!(w, "# TYPE {prefix}{family} {kind}")?;
+            if let Some(unit) = def.unit {
+                writeln!(w, "# UNIT {prefix}{family} {unit}")?;
+            }
+            match def.kind {
+                Kind::Counter => {
+                    writeln!(w, "{prefix}{family}_total {}", FloatValue(v))?;
+                    if let Some(t) = self.start_time_seconds {
+                        writeln!(w, "{prefix}{family}_created {t}")?;
+                    }
+                }
+                Kind::Gauge => writeln!(w, "{prefix}{family} {}", FloatValue(v))?,
+            }
+        }
+        if let Some(policy) = self.sched_policy {
+            writeln!(w, "# HELP {prefix}{SCHED_INFO_FAMILY} {SCHED_INFO_HELP}")?;
+            writeln!(w, "# TYPE {prefix}{SCHED_INFO_FAMILY} info")?;
+            writeln!(
+                w,
+                "{prefix}{SCHED_INFO_NAME}{{policy=\"{}\"}} 1",
+                policy.as_str()
+            )?;
+        }
+        writeln!(w, "# EOF")
+    }
+}
+
+// Formats a sample value, spelling the special values the way Prometheus parses them.
+struct FloatValue(f64);
+
+impl std::fmt::Display for FloatValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let v = self.0;
+        if v.is_nan() {
+            f.write_str("NaN")
+        } else if v == f64::INFINITY {
+            f.write_str("+Inf")
+        } else if v == f64::NEG_INFINITY {
+            f.write_str("-Inf")
+        } else {
+            write!(f, "{v}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use openmetrics_parser::openmetrics::parse_openmetrics;
+    use openmetrics_parser::prometheus::parse_prometheus;
+    use openmetrics_parser::{
+        MetricsExposition, OpenMetricsType, OpenMetricsValue, PrometheusType, PrometheusValue,
+    };
+
+    use super::*;
+    use crate::collector::SchedPolicy;
+
+    #[test]
+    fn test_write_prometheus_text() {
+        let metrics = Metrics {
+            cpu_seconds_total: Some(1.5),
+            open_fds: Some(8),
+            resident_memory_bytes: Some(4096),
+            start_time_seconds: Some(1_700_000_000),
+            sched_policy: Some(SchedPolicy::Other),
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        metrics.write_prometheus_text(&mut buf, "my_").unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            text,
+            concat!(
+                "# HELP my_process_cpu_seconds_total Total user and system CPU time spent in seconds.\n",
+                "# TYPE my_process_cpu_seconds_total counter\n",
+                "my_process_cpu_seconds_total 1.5\n",
+                "# HELP my_process_open_fds Number of open file descriptors.\n",
+                "# TYPE my_process_open_fds gauge\n",
+                "my_process_open_fds 8\n",
+                "# HELP my_process_resident_memory_bytes Resident memory size in bytes.\n",
+                "# TYPE my_process_resident_memory_bytes gauge\n",
+                "my_process_resident_memory_bytes 4096\n",
+                "# HELP my_process_start_time_seconds Start time of the process since unix epoch in seconds.\n",
+                "# TYPE my_process_start_time_seconds gauge\n",
+                "my_process_start_time_seconds 1700000000\n",
+                "# HELP my_process_sched_info Scheduling policy of the process, labeled by policy.\n",
+                "# TYPE my_process_sched_info gauge\n",
+                "my_process_sched_info{policy=\"SCHED_OTHER\"} 1\n",
+            )
+        );
+        assert_prometheus(&text, &metrics, "my_");
+    }
+
+    #[test]
+    fn test_write_openmetrics_text() {
+        let metrics = Metrics {
+            cpu_seconds_total: Some(1.5),
+            open_fds: Some(8),
+            resident_memory_bytes: Some(4096),
+            start_time_seconds: Some(1_700_000_000),
+            sched_policy: Some(SchedPolicy::Other),
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        metrics.write_openmetrics_text(&mut buf, "my_").unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            text,
+            concat!(
+                "# HELP my_process_cpu_seconds Total user and system CPU time spent in seconds.\n",
+                "# TYPE my_process_cpu_seconds counter\n",
+                "# UNIT my_process_cpu_seconds seconds\n",
+                "my_process_cpu_seconds_total 1.5\n",
+                "my_process_cpu_seconds_created 1700000000\n",
+                "# HELP my_process_open_fds Number of open file descriptors.\n",
+                "# TYPE my_process_open_fds gauge\n",
+                "my_process_open_fds 8\n",
+                "# HELP my_process_resident_memory_bytes Resident memory size in bytes.\n",
+                "# TYPE my_process_resident_memory_bytes gauge\n",
+                "# UNIT my_process_resident_memory_bytes bytes\n",
+                "my_process_resident_memory_bytes 4096\n",
+                "# HELP my_process_start_time_seconds Start time of the process since unix epoch in seconds.\n",
+                "# TYPE my_process_start_time_seconds gauge\n",
+                "# UNIT my_process_start_time_seconds seconds\n",
+                "my_process_start_time_seconds 1700000000\n",
+                "# HELP my_process_sched Scheduling policy of the process, labeled by policy.\n",
+                "# TYPE my_process_sched info\n",
+                "my_process_sched_info{policy=\"SCHED_OTHER\"} 1\n",
+                "# EOF\n",
+            )
+        );
+        assert_openmetrics(&text, &metrics, "my_");
+    }
+
+    #[test]
+    fn test_write_text_collect() {
+        let metrics = crate::collector::collect();
+        let mut buf = Vec::new();
+        metrics.write_prometheus_text(&mut buf, "").unwrap();
+        assert_prometheus(&String::from_utf8(buf).unwrap(), &metrics, "");
+        let mut buf = Vec::new();
+        metrics.write_openmetrics_text(&mut buf, "").unwrap();
+        assert_openmetrics(&String::from_utf8(buf).unwrap(), &metrics, "");
+    }
+
+    // Parse the Prometheus text with openmetrics-parser, which rejects malformed lines, and check
+    // that every metric is parsed with its type, help string and value.
+    fn assert_prometheus(text: &str, metrics: &Metrics, prefix: &str) {
+        // The parser rejects an exposition without any metric family
+        let exposition = if text.is_empty() {
+            MetricsExposition::new()
+        } else {
+            parse_prometheus(text).unwrap_or_else(|e| panic!("{:?}\n{}", e, text))
+        };
+        let mut families = 0;
+        for def in DEFINITIONS {
+            let family = exposition.families.get(&format!("{prefix}{}", def.name));
+            let (v, family) = match ((def.value)(metrics), family) {
+                (Some(v), Some(family)) => (v, family),
+                (None, None) => continue,
+                (v, _) => panic!("unexpected family of {}: {:?}\n{}", def.name, v, text),
+            };
+            families += 1;
+            assert_eq!(family.help, def.help);
+            let samples: Vec<_> = family.iter_samples().collect();
+            assert_eq!(samples.len(), 1, "{}", text);
+            match (&def.kind, &samples[0].value) {
+                (Kind::Counter, PrometheusValue::Counter(c)) => assert_eq!(c.value.as_f64(), v),
+                (Kind::Gauge, PrometheusValue::Gauge(n)) => assert_eq!(n.as_f64(), v),
+                (_, value) => panic!("unexpected value of {}: {:?}", def.name, value),
+            }
+        }
+        if let Some(policy) = metrics.sched_policy {
+            let family = &exposition.families[&format!("{prefix}{SCHED_INFO_NAME}")];
+            families += 1;
+            assert_eq!(family.family_type, PrometheusType::Gauge);
+            assert_eq!(family.help, SCHED_INFO_HELP);
+            let labels: Vec<_> = family
+                .iter_samples()
+                .map(|s| {
+                    s.get_labelset()
+                        .unwrap()
+                        .get_label_value("policy")
+                        .unwrap()
+                        .to_string()
+                })
+                .collect();
+            assert_eq!(labels, [policy.as_str()]);
+        }
+        assert_eq!(exposition.families.len(), families, "{}", text);
+    }
+
+    // Parse the OpenMetrics text with openmetrics-parser and check the rules of the specification
+    // that it does not enforce itself.
+    fn parse_openmetrics_strict(
+        text: &str,
+    ) -> Result<MetricsExposition<OpenMetricsType, OpenMetricsValue>, String> {
+        if !text.ends_with("# EOF\n") {
+            return Err("the exposition must end with `# EOF` and a newline".to_string());
+        }
+        // The parser rejects an exposition without any metric family
+        if text == "# EOF\n" {
+            return Ok(MetricsExposition::new());
+        }
+        let exposition = parse_openmetrics(text).map_err(|e| format!("{:?}", e))?;
+        for family in exposition.families.values() {
+            if !family.unit.is_empty()
+                && !family.family_name.ends_with(&format!("_{}", family.unit))
+            {
+                return Err(format!(
+                    "the name of {} must end with its unit {}",
+                    family.family_name, family.unit
+                ));
+            }
+        }
+        Ok(exposition)
+    }
+
+    // Parse the OpenMetrics text strictly and check that every metric is parsed with its type,
+    // unit, help string, value and created timestamp.
+    fn assert_openmetrics(text: &str, metrics: &Metrics, prefix: &str) {
+        let exposition =
+            parse_openmetrics_strict(text).unwrap_or_else(|e| panic!("{}\n{}", e, text));
+        let mut families = 0;
+        for def in DEFINITIONS {
+            let family = exposition
+                .families
+                .get(&format!("{prefix}{}", def.family_name()));
+            let (v, family) = match ((def.value)(metrics), family) {
+                (Some(v), Some(family)) => (v, family),
+                (None, None) => continue,
+                (v, _) => panic!("unexpected family of {}: {:?}\n{}", def.name, v, text),
+            };
+            families += 1;
+            assert_eq!(family.help, def.help);
+            assert_eq!(family.unit, def.unit.unwrap_or_default());
+            let samples: Vec<_> = family.iter_samples().collect();
+            assert_eq!(samples.len(), 1, "{}", text);
+            match (&def.kind, &samples[0].value) {
+                (Kind::Counter, OpenMetricsValue::Counter(c)) => {
+                    assert_eq!(c.value.as_f64(), v);
+                    assert_eq!(c.created, metrics.start_time_seconds.map(|t| t as f64));
+                }
+                (Kind::Gauge, OpenMetricsValue::Gauge(n)) => assert_eq!(n.as_f64(), v),
+                (_, value) => panic!("unexpected value of {}: {:?}", def.name, value),
+            }
+        }
+        if let Some(policy) = metrics.sched_policy {
+            let family = &exposition.families[&format!("{prefix}{SCHED_INFO_FAMILY}")];
+            families += 1;
+            assert_eq!(family.family_type, OpenMetricsType::Info);
+            assert_eq!(family.help, SCHED_INFO_HELP);
+            let labels: Vec<_> = family
+                .iter_samples()
+                .map(|s| {
+                    s.get_labelset()
+                        .unwrap()
+                        .get_label_value("policy")
+                        .unwrap()
+                        .to_string()
+                })
+                .collect();
+            assert_eq!(labels, [policy.as_str()]);
+        }
+        assert_eq!(exposition.families.len(), families, "{}", text);
+    }
+
+    #[test]
+    fn test_parse_openmetrics_strict() {
+        let metrics = Metrics {
+            cpu_seconds_total: Some(1.5),
+            open_fds: Some(8),
+            resident_memory_bytes: Some(4096),
+            start_time_seconds: Some(1_700_000_000),
+            sched_policy: Some(SchedPolicy::Other),
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        metrics.write_openmetrics_text(&mut buf, "").unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert!(parse_openmetrics_strict(&text).is_ok());
+
+        let created = "process_cpu_seconds_created 1700000000\n";
+        for (what, broken) in [
+            ("missing EOF", text.replace("# EOF\n", "")),
+            ("text after EOF", text.clone() + "process_open_fds 8\n"),
+            (
+                "unit not suffixed",
+                text.replace(
+                    "# UNIT process_resident_memory_bytes bytes",
+                    "# UNIT process_resident_memory_bytes seconds",
+                ),
+            ),
+            (
+                "misplaced _created",
+                text.replace(created, "").replace(
+                    "process_open_fds 8\n",
+                    &format!("process_open_fds 8\n{created}"),
+                ),
+            ),
+            (
+                "_created of a gauge",
+                text.replace(
+                    "process_open_fds 8\n",
+                    "process_open_fds 8\nprocess_open_fds_created 1700000000\n",
+                ),
+            ),
+            (
+                "counter without _total",
+                text.replace("process_cpu_seconds_total", "process_cpu_seconds"),
+            ),
+            (
+                "info without _info",
+                text.replace("process_sched_info{", "process_sched{"),
+            ),
+            (
+                "unit of info",
+                text.replace(
+                    "# TYPE process_sched info\n",
+                    "# TYPE process_sched info\n# UNIT process_sched seconds\n",
+                ),
+            ),
+        ] {
+            assert!(
+                parse_openmetrics_strict(&broken).is_err(),
+                "{} must be rejected:\n{}",
+                what,
+                broken
+            );
+        }
+    }
+
+    #[test]
+    fn test_float_value() {
+        assert_eq!(FloatValue(0.25).to_string(), "0.25");
+        assert_eq!(FloatValue(3.0).to_string(), "3");
+        assert_eq!(FloatValue(f64::INFINITY).to_string(), "+Inf");
+        assert_eq!(FloatValue(f64::NEG_INFINITY).to_string(), "-Inf");
+        assert_eq!(FloatValue(f64::NAN).to_string(), "NaN");
+    }
+}
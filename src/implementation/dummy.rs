@@ -1,5 +1,27 @@
-use super::Metrics;
+use super::{
+    Error, Errors, Field, MallocStats, MetricSet, Metrics, NumaMemory, ProcessInfo, ThreadStates,
+};
 
-pub fn collect() -> Metrics {
-    Metrics::default()
+pub const UNSUPPORTED_FIELDS: &[Field] = Field::ALL;
+
+pub fn try_collect(_set: MetricSet) -> (Metrics, Errors) {
+    let mut errors = Errors::default();
+    errors.insert_all(UNSUPPORTED_FIELDS, Error::Unsupported);
+    (Metrics::default(), errors)
+}
+
+pub fn collect_thread_states() -> Option<ThreadStates> {
+    None
+}
+
+pub fn collect_numa_memory() -> Option<NumaMemory> {
+    None
+}
+
+pub fn collect_malloc_stats() -> Option<MallocStats> {
+    None
+}
+
+pub fn collect_process_info() -> Option<ProcessInfo> {
+    None
 }
@@ -1,24 +1,28 @@
-use super::Metrics;
+use std::io;
 
-fn getrusage(who: libc::c_int) -> Option<libc::rusage> {
+use super::{
+    Error, Errors, Field, MallocStats, MetricSet, Metrics, NumaMemory, ProcessInfo, ThreadStates,
+};
+
+fn getrusage(who: libc::c_int) -> io::Result<libc::rusage> {
     let mut usage = std::mem::MaybeUninit::zeroed();
     // SAFETY: libc call; usage is valid pointer to rusage struct
     if unsafe { libc::getrusage(who, usage.as_mut_ptr()) } == 0 {
         // SAFETY: libc call was success, struct must be initialized
-        Some(unsafe { usage.assume_init() })
+        Ok(unsafe { usage.assume_init() })
     } else {
-        None
+        Err(io::Error::last_os_error())
     }
 }
 
-fn getrlimit(resource: libc::c_int) -> Option<libc::rlimit> {
+fn getrlimit(resource: libc::c_int) -> io::Result<libc::rlimit> {
     let mut limit = std::mem::MaybeUninit::zeroed();
     // SAFETY: libc call; limit is valid pointer to rlimit struct
     if unsafe { libc::getrlimit(resource, limit.as_mut_ptr()) } == 0 {
         // SAFETY: libc call was success, struct must be initialized
-        Some(unsafe { limit.assume_init() })
+        Ok(unsafe { limit.assume_init() })
     } else {
-        None
+        Err(io::Error::last_os_error())
     }
 }
 
@@ -30,7 +34,7 @@ fn translate_rlim(rlim: libc::rlim_t) -> u64 {
     }
 }
 
-fn kinfo_getproc(pid: libc::pid_t) -> Option<libc::kinfo_proc> {
+fn kinfo_getproc(pid: libc::pid_t) -> io::Result<libc::kinfo_proc> {
     // References:
     // kinfo_getproc() code from FreeBSD: https://github.com/freebsd/freebsd-src/blob/b22be3bbb2de75157c97d8baa01ce6cd654caddf/lib/libutil/kinfo_getproc.c
     // code from deno doing similar stuff: https://github.com/denoland/deno/blob/20ae8db50d7d48ad020b83ebe78dc0e9e9eab3b2/runtime/ops/os/mod.rs#L415
@@ -42,7 +46,7 @@ fn kinfo_getproc(pid: libc::pid_t) -> Option<libc::kinfo_proc> {
 
     // SAFETY: libc call; mib is statically initialized, kinfo_proc is valid pointer
     // to kinfo_proc and data_size holds its size
-    if unsafe {
+    let ret = unsafe {
         libc::sysctl(
             mib.as_ptr(),
             mib.len() as _,
@@ -51,51 +55,118 @@ fn kinfo_getproc(pid: libc::pid_t) -> Option<libc::kinfo_proc> {
             std::ptr::null(),
             0,
         )
-    } == 0
-        && data_size == kinfo_proc_size
-    {
-        // SAFETY: libc call was success and check for struct size passed, struct must be initialized
-        Some(unsafe { kinfo_proc.assume_init() })
+    };
+    if ret != 0 {
+        Err(io::Error::last_os_error())
+    } else if data_size != kinfo_proc_size {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unexpected size of kinfo_proc",
+        ))
     } else {
-        None
+        // SAFETY: libc call was success and check for struct size passed, struct must be initialized
+        Ok(unsafe { kinfo_proc.assume_init() })
     }
 }
 
-pub fn collect() -> Metrics {
-    let mut metrics = Metrics::default();
+// Fields read from getrusage()
+const CPU_FIELDS: &[Field] = &[
+    Field::CpuSecondsTotal,
+    Field::CpuUserSecondsTotal,
+    Field::CpuSystemSecondsTotal,
+];
+
+// Fields read from kinfo_proc
+const KINFO_PROC_FIELDS: &[Field] = &[
+    Field::VirtualMemoryBytes,
+    Field::ResidentMemoryBytes,
+    Field::StartTimeSeconds,
+    Field::Threads,
+];
+
+// Metrics read from kinfo_proc
+const KINFO_PROC_SET: MetricSet = MetricSet::MEMORY
+    .union(MetricSet::START_TIME)
+    .union(MetricSet::THREADS);
+
+// Fields that are not supported on FreeBSD
+pub const UNSUPPORTED_FIELDS: &[Field] = &[
+    Field::IoWaitSecondsTotal,
+    Field::Nice,
+    Field::Priority,
+    Field::SchedPolicy,
+    Field::RtPriority,
+    Field::CpuAffinity,
+];
+
+fn out_of_range() -> Error {
+    io::Error::new(io::ErrorKind::InvalidData, "value out of range").into()
+}
 
-    if let Some(usage) = getrusage(libc::RUSAGE_SELF) {
-        metrics.cpu_seconds_total = Some(
-            (usage.ru_utime.tv_sec + usage.ru_stime.tv_sec) as f64
-                + (usage.ru_utime.tv_usec + usage.ru_stime.tv_usec) as f64 / 1000000.0,
-        );
+pub fn try_collect(set: MetricSet) -> (Metrics, Errors) {
+    let mut metrics = Metrics::default();
+    let mut errors = Errors::default();
+
+    if set.contains(MetricSet::CPU) {
+        match getrusage(libc::RUSAGE_SELF) {
+            Ok(usage) => {
+                metrics.cpu_seconds_total = Some(
+                    (usage.ru_utime.tv_sec + usage.ru_stime.tv_sec) as f64
+                        + (usage.ru_utime.tv_usec + usage.ru_stime.tv_usec) as f64 / 1000000.0,
+                );
+                metrics.cpu_user_seconds_total =
+                    Some(usage.ru_utime.tv_sec as f64 + usage.ru_utime.tv_usec as f64 / 1000000.0);
+                metrics.cpu_system_seconds_total =
+                    Some(usage.ru_stime.tv_sec as f64 + usage.ru_stime.tv_usec as f64 / 1000000.0);
+            }
+            Err(e) => errors.insert_all(CPU_FIELDS, e.into()),
+        }
     }
 
-    if let Some(limit_as) = getrlimit(libc::RLIMIT_AS) {
-        metrics.virtual_memory_max_bytes = Some(translate_rlim(limit_as.rlim_cur));
+    if set.contains(MetricSet::MEMORY) {
+        match getrlimit(libc::RLIMIT_AS) {
+            Ok(limit_as) => {
+                metrics.virtual_memory_max_bytes = Some(translate_rlim(limit_as.rlim_cur));
+            }
+            Err(e) => errors.insert(Field::VirtualMemoryMaxBytes, e.into()),
+        }
     }
 
-    if let Some(limit_as) = getrlimit(libc::RLIMIT_NOFILE) {
-        metrics.max_fds = Some(translate_rlim(limit_as.rlim_cur));
+    if set.contains(MetricSet::FDS) {
+        match getrlimit(libc::RLIMIT_NOFILE) {
+            Ok(limit_as) => metrics.max_fds = Some(translate_rlim(limit_as.rlim_cur)),
+            Err(e) => errors.insert(Field::MaxFds, e.into()),
+        }
     }
 
     // SAFETY: libc call
     let pid = unsafe { libc::getpid() };
 
-    if let Some(kinfo_proc) = kinfo_getproc(pid) {
-        // struct kinfo_proc layout for reference
-        // libc crate: https://docs.rs/libc/latest/x86_64-unknown-freebsd/libc/struct.kinfo_proc.html
-        // FreeBSD: https://github.com/freebsd/freebsd-src/blob/b22be3bbb2de75157c97d8baa01ce6cd654caddf/lib/libutil/kinfo_getfile.c
-
-        // SAFETY: libc call
-        let pagesize = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
-        metrics.virtual_memory_bytes = Some(kinfo_proc.ki_size as u64);
-        metrics.resident_memory_bytes = Some(kinfo_proc.ki_rssize as u64 * pagesize);
-        use std::convert::TryInto as _;
-        metrics.start_time_seconds = kinfo_proc.ki_start.tv_sec.try_into().ok();
-        metrics.threads = kinfo_proc.ki_numthreads.try_into().ok();
-
-        // note that we can't access pointers in kinfo_proc as these point to kernel space
+    if set.intersects(KINFO_PROC_SET) {
+        match kinfo_getproc(pid) {
+            Ok(kinfo_proc) => {
+                // struct kinfo_proc layout for reference
+                // libc crate: https://docs.rs/libc/latest/x86_64-unknown-freebsd/libc/struct.kinfo_proc.html
+                // FreeBSD: https://github.com/freebsd/freebsd-src/blob/b22be3bbb2de75157c97d8baa01ce6cd654caddf/lib/libutil/kinfo_getfile.c
+
+                // SAFETY: libc call
+                let pagesize = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+                metrics.virtual_memory_bytes = Some(kinfo_proc.ki_size as u64);
+                metrics.resident_memory_bytes = Some(kinfo_proc.ki_rssize as u64 * pagesize);
+                use std::convert::TryInto as _;
+                match kinfo_proc.ki_start.tv_sec.try_into() {
+                    Ok(v) => metrics.start_time_seconds = Some(v),
+                    Err(_) => errors.insert(Field::StartTimeSeconds, out_of_range()),
+                }
+                match kinfo_proc.ki_numthreads.try_into() {
+                    Ok(v) => metrics.threads = Some(v),
+                    Err(_) => errors.insert(Field::Threads, out_of_range()),
+                }
+
+                // note that we can't access pointers in kinfo_proc as these point to kernel space
+            }
+            Err(e) => errors.insert_all(KINFO_PROC_FIELDS, e.into()),
+        }
     }
 
     // Alternative to this would be implementing kinfo_getfile() like interface, see
@@ -104,9 +175,29 @@ pub fn collect() -> Metrics {
     // because we have to parse structures of varying size frow raw memory. As long as
     // it's common to read /proc on Linux, it shuld be as ok to read /dev/fs (which
     // is roughly the same as /proc/self/fd) on FreeBSD.
-    metrics.open_fds = std::fs::read_dir("/dev/fd")
-        .ok()
-        .map(|read_dir| read_dir.count() as u64);
+    if set.contains(MetricSet::FDS) {
+        match std::fs::read_dir("/dev/fd") {
+            Ok(read_dir) => metrics.open_fds = Some(read_dir.count() as u64),
+            Err(e) => errors.insert(Field::OpenFds, e.into()),
+        }
+    }
+
+    errors.insert_all(UNSUPPORTED_FIELDS, Error::Unsupported);
+    (metrics, errors)
+}
+
+pub fn collect_thread_states() -> Option<ThreadStates> {
+    None
+}
+
+pub fn collect_numa_memory() -> Option<NumaMemory> {
+    None
+}
+
+pub fn collect_malloc_stats() -> Option<MallocStats> {
+    None
+}
 
-    metrics
+pub fn collect_process_info() -> Option<ProcessInfo> {
+    None
 }
@@ -1,36 +1,365 @@
+use std::collections::BTreeMap;
+
 use once_cell::sync::Lazy;
 use procfs::prelude::*;
-use procfs::process::{LimitValue, Process};
+use procfs::process::{LimitValue, ProcState, Process};
 
-use super::Metrics;
+use super::{
+    Error, Errors, Field, MallocStats, MetricSet, Metrics, NumaMemory, ProcessInfo, SchedPolicy,
+    ThreadStates,
+};
 
 static TICKS_PER_SECOND: Lazy<f64> = Lazy::new(|| procfs::ticks_per_second() as f64);
-static BOOT_TIME_SECS: Lazy<Option<u64>> = Lazy::new(|| procfs::boot_time_secs().ok());
+static BOOT_TIME_SECS: Lazy<Result<u64, Error>> =
+    Lazy::new(|| procfs::boot_time_secs().map_err(Error::from));
+
+fn sched_policy(policy: u32) -> SchedPolicy {
+    // https://github.com/torvalds/linux/blob/v6.6/include/uapi/linux/sched.h#L111-L117
+    match policy {
+        0 => SchedPolicy::Other,
+        1 => SchedPolicy::Fifo,
+        2 => SchedPolicy::RoundRobin,
+        3 => SchedPolicy::Batch,
+        5 => SchedPolicy::Idle,
+        6 => SchedPolicy::Deadline,
+        v => SchedPolicy::Unknown(v),
+    }
+}
+
+fn cpu_affinity() -> Result<u64, Error> {
+    // Start from the size of cpu_set_t (1024 CPUs) and grow the mask the way CPU_ALLOC does,
+    // since sched_getaffinity fails with EINVAL when the mask is smaller than the kernel's.
+    const MAX_CPUS: usize = 1 << 22;
+    let word_bits = 8 * std::mem::size_of::<libc::c_ulong>();
+    let mut cpus = 8 * std::mem::size_of::<libc::cpu_set_t>();
+    loop {
+        let mut mask: Vec<libc::c_ulong> = vec![0; cpus / word_bits];
+        let size = mask.len() * std::mem::size_of::<libc::c_ulong>();
+        // SAFETY: libc call; mask is a valid, zeroed buffer of size bytes, which is a multiple
+        // of the size of c_ulong as sched_getaffinity requires, and cpu_set_t is a bare array
+        // of such words
+        let ret =
+            unsafe { libc::sched_getaffinity(0, size, mask.as_mut_ptr() as *mut libc::cpu_set_t) };
+        if ret == 0 {
+            return Ok(mask.iter().map(|w| u64::from(w.count_ones())).sum());
+        }
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::EINVAL) || cpus >= MAX_CPUS {
+            return Err(err.into());
+        }
+        cpus *= 2;
+    }
+}
+
+// Fields read from /proc/self/stat
+const STAT_FIELDS: &[Field] = &[
+    Field::StartTimeSeconds,
+    Field::CpuSecondsTotal,
+    Field::CpuUserSecondsTotal,
+    Field::CpuSystemSecondsTotal,
+    Field::ResidentMemoryBytes,
+    Field::VirtualMemoryBytes,
+    Field::Threads,
+    Field::IoWaitSecondsTotal,
+    Field::Nice,
+    Field::Priority,
+    Field::SchedPolicy,
+    Field::RtPriority,
+];
+
+// Fields read from /proc/self/limits
+const LIMITS_FIELDS: &[Field] = &[Field::MaxFds, Field::VirtualMemoryMaxBytes];
+
+// Metrics read from /proc/self/stat
+const STAT_SET: MetricSet = MetricSet::CPU
+    .union(MetricSet::MEMORY)
+    .union(MetricSet::START_TIME)
+    .union(MetricSet::THREADS)
+    .union(MetricSet::IO)
+    .union(MetricSet::SCHED);
+
+// Metrics read from /proc/self/limits
+const LIMITS_SET: MetricSet = MetricSet::FDS.union(MetricSet::MEMORY);
+
+// Fields that are not supported on Linux. Some of them may still be missing on kernels built
+// without the corresponding accounting.
+#[cfg_attr(not(feature = "metrics-rs"), allow(dead_code))]
+pub const UNSUPPORTED_FIELDS: &[Field] = &[];
 
-pub fn collect() -> Metrics {
+pub fn try_collect(set: MetricSet) -> (Metrics, Errors) {
     let mut metrics = Metrics::default();
-    if let Ok(proc) = Process::myself() {
-        if let Ok(stat) = proc.stat() {
-            if let Some(bts) = *BOOT_TIME_SECS {
-                metrics.start_time_seconds =
-                    Some(bts + ((stat.starttime as f64) / *TICKS_PER_SECOND) as u64);
+    let mut errors = Errors::default();
+    match Process::myself() {
+        Ok(proc) => {
+            if set.intersects(STAT_SET) {
+                match proc.stat() {
+                    Ok(stat) => {
+                        match &*BOOT_TIME_SECS {
+                            Ok(bts) => {
+                                metrics.start_time_seconds = Some(
+                                    bts + ((stat.starttime as f64) / *TICKS_PER_SECOND) as u64,
+                                );
+                            }
+                            Err(e) => errors.insert(Field::StartTimeSeconds, e.clone()),
+                        }
+                        metrics.cpu_seconds_total =
+                            Some((stat.utime + stat.stime) as f64 / *TICKS_PER_SECOND);
+                        metrics.cpu_user_seconds_total =
+                            Some(stat.utime as f64 / *TICKS_PER_SECOND);
+                        metrics.cpu_system_seconds_total =
+                            Some(stat.stime as f64 / *TICKS_PER_SECOND);
+                        metrics.resident_memory_bytes = Some(stat.rss_bytes().get());
+                        metrics.virtual_memory_bytes = Some(stat.vsize);
+                        metrics.threads = Some(stat.num_threads as u64);
+                        metrics.nice = Some(stat.nice);
+                        metrics.priority = Some(stat.priority);
+                        // These fields are missing on old kernels
+                        metrics.io_wait_seconds_total = stat
+                            .delayacct_blkio_ticks
+                            .map(|v| v as f64 / *TICKS_PER_SECOND);
+                        if metrics.io_wait_seconds_total.is_none() {
+                            errors.insert(Field::IoWaitSecondsTotal, Error::Unsupported);
+                        }
+                        metrics.sched_policy = stat.policy.map(sched_policy);
+                        if metrics.sched_policy.is_none() {
+                            errors.insert(Field::SchedPolicy, Error::Unsupported);
+                        }
+                        metrics.rt_priority = stat.rt_priority.map(u64::from);
+                        if metrics.rt_priority.is_none() {
+                            errors.insert(Field::RtPriority, Error::Unsupported);
+                        }
+                    }
+                    Err(e) => errors.insert_all(STAT_FIELDS, e.into()),
+                }
             }
-            metrics.cpu_seconds_total = Some((stat.utime + stat.stime) as f64 / *TICKS_PER_SECOND);
-            metrics.resident_memory_bytes = Some(stat.rss_bytes().get());
-            metrics.virtual_memory_bytes = Some(stat.vsize);
-            metrics.threads = Some(stat.num_threads as u64);
-        }
-        metrics.open_fds = proc.fd_count().ok().map(|v| v as u64);
-        if let Ok(limit) = proc.limits() {
-            metrics.max_fds = match limit.max_open_files.soft_limit {
-                LimitValue::Value(v) => Some(v),
-                LimitValue::Unlimited => Some(0),
-            };
-            metrics.virtual_memory_max_bytes = match limit.max_address_space.soft_limit {
-                LimitValue::Value(v) => Some(v),
-                LimitValue::Unlimited => Some(0),
+            // Counting fds walks the whole fd table so it is the most expensive part
+            if set.contains(MetricSet::FDS) {
+                match proc.fd_count() {
+                    Ok(v) => metrics.open_fds = Some(v as u64),
+                    Err(e) => errors.insert(Field::OpenFds, e.into()),
+                }
+            }
+            if set.intersects(LIMITS_SET) {
+                match proc.limits() {
+                    Ok(limit) => {
+                        metrics.max_fds = match limit.max_open_files.soft_limit {
+                            LimitValue::Value(v) => Some(v),
+                            LimitValue::Unlimited => Some(0),
+                        };
+                        metrics.virtual_memory_max_bytes = match limit.max_address_space.soft_limit
+                        {
+                            LimitValue::Value(v) => Some(v),
+                            LimitValue::Unlimited => Some(0),
+                        };
+                    }
+                    Err(e) => errors.insert_all(LIMITS_FIELDS, e.into()),
+                }
+            }
+        }
+        Err(e) => {
+            let e = Error::from(e);
+            errors.insert_all(STAT_FIELDS, e.clone());
+            errors.insert(Field::OpenFds, e.clone());
+            errors.insert_all(LIMITS_FIELDS, e);
+        }
+    }
+    if set.contains(MetricSet::SCHED) {
+        match cpu_affinity() {
+            Ok(v) => metrics.cpu_affinity = Some(v),
+            Err(e) => errors.insert(Field::CpuAffinity, e),
+        }
+    }
+    (metrics, errors)
+}
+
+pub fn collect_thread_states() -> Option<ThreadStates> {
+    let proc = Process::myself().ok()?;
+    let mut states = ThreadStates::default();
+    // Threads may exit while we walk /proc/self/task, so failures on an individual task are
+    // ignored rather than failing the whole snapshot.
+    for state in proc
+        .tasks()
+        .ok()?
+        .flatten()
+        .filter_map(|task| task.stat().ok())
+        .filter_map(|stat| stat.state().ok())
+    {
+        match state {
+            ProcState::Running | ProcState::Waking => states.running += 1,
+            ProcState::Sleeping | ProcState::Idle | ProcState::Parked => states.sleeping += 1,
+            ProcState::Waiting | ProcState::Wakekill => states.disk_sleep += 1,
+            ProcState::Stopped | ProcState::Tracing => states.stopped += 1,
+            ProcState::Zombie | ProcState::Dead => states.zombie += 1,
+        }
+    }
+    Some(states)
+}
+
+pub fn collect_numa_memory() -> Option<NumaMemory> {
+    // procfs does not parse numa_maps so we read it by ourselves. It does not exist on kernels
+    // built without CONFIG_NUMA.
+    let content = std::fs::read_to_string("/proc/self/numa_maps").ok()?;
+    Some(NumaMemory {
+        resident_bytes: parse_numa_maps(&content),
+    })
+}
+
+// Each line of numa_maps describes a memory mapping like
+//
+//   7f4c2c000000 default anon=1 dirty=1 N0=1 N1=3 kernelpagesize_kB=4
+//
+// where `N<node>=<pages>` is the number of pages of the mapping resident on the node.
+// https://man7.org/linux/man-pages/man7/numa.7.html
+fn parse_numa_maps(content: &str) -> BTreeMap<u32, u64> {
+    let mut nodes = BTreeMap::new();
+    for line in content.lines() {
+        let mut pages = Vec::new();
+        let mut pagesize = 4096;
+        for field in line.split_whitespace().skip(1) {
+            let (key, value) = match field.split_once('=') {
+                Some(v) => v,
+                None => continue,
             };
+            if key == "kernelpagesize_kB" {
+                if let Ok(v) = value.parse::<u64>() {
+                    pagesize = v * 1024;
+                }
+            } else if let Some(node) = key.strip_prefix('N') {
+                if let (Ok(node), Ok(v)) = (node.parse::<u32>(), value.parse::<u64>()) {
+                    pages.push((node, v));
+                }
+            }
         }
+        for (node, v) in pages {
+            *nodes.entry(node).or_insert(0) += v * pagesize;
+        }
+    }
+    nodes
+}
+
+// mallinfo2() is only available since glibc 2.33 so it is looked up at runtime. Linking against
+// it directly would prevent the binary from starting with an older glibc.
+#[cfg(target_env = "gnu")]
+static MALLINFO2: Lazy<Option<unsafe extern "C" fn() -> libc::mallinfo2>> = Lazy::new(|| {
+    let name = b"mallinfo2\0";
+    // SAFETY: libc call; name is a nul terminated string
+    let sym = unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr() as *const libc::c_char) };
+    if sym.is_null() {
+        None
+    } else {
+        // SAFETY: the symbol is mallinfo2() of glibc which has the signature above
+        Some(unsafe {
+            std::mem::transmute::<*mut libc::c_void, unsafe extern "C" fn() -> libc::mallinfo2>(sym)
+        })
+    }
+});
+
+#[cfg(target_env = "gnu")]
+pub fn collect_malloc_stats() -> Option<MallocStats> {
+    let mallinfo2 = (*MALLINFO2)?;
+    // SAFETY: libc call
+    let info = unsafe { mallinfo2() };
+    Some(MallocStats {
+        arena_bytes: info.arena as u64,
+        in_use_bytes: info.uordblks as u64,
+        free_bytes: info.fordblks as u64,
+        mmap_bytes: info.hblkhd as u64,
+    })
+}
+
+#[cfg(not(target_env = "gnu"))]
+pub fn collect_malloc_stats() -> Option<MallocStats> {
+    None
+}
+
+pub fn collect_process_info() -> Option<ProcessInfo> {
+    let proc = Process::myself().ok()?;
+    let mut info = ProcessInfo {
+        pid: proc.pid as u64,
+        ..Default::default()
+    };
+    if let Ok(status) = proc.status() {
+        info.ppid = Some(status.ppid as u64);
+        info.user = Some(user_name(status.euid).unwrap_or_else(|| status.euid.to_string()));
+        info.group = Some(group_name(status.egid).unwrap_or_else(|| status.egid.to_string()));
+    }
+    info.exe = proc
+        .exe()
+        .ok()
+        .and_then(|exe| exe.file_name().map(|v| v.to_string_lossy().into_owned()));
+    info.cmdline = proc.cmdline().ok();
+    Some(info)
+}
+
+fn user_name(uid: libc::uid_t) -> Option<String> {
+    let mut passwd = std::mem::MaybeUninit::<libc::passwd>::zeroed();
+    let mut buf = vec![0 as libc::c_char; 1024];
+    let mut result = std::ptr::null_mut();
+    // SAFETY: libc call; passwd, buf and result are valid pointers and buf.len() is the size of
+    // buf
+    let ret = unsafe {
+        libc::getpwuid_r(
+            uid,
+            passwd.as_mut_ptr(),
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if ret != 0 || result.is_null() {
+        return None;
+    }
+    // SAFETY: libc call was success, pw_name points to a nul terminated string in buf
+    let name = unsafe { std::ffi::CStr::from_ptr((*result).pw_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+fn group_name(gid: libc::gid_t) -> Option<String> {
+    let mut group = std::mem::MaybeUninit::<libc::group>::zeroed();
+    let mut buf = vec![0 as libc::c_char; 1024];
+    let mut result = std::ptr::null_mut();
+    // SAFETY: libc call; group, buf and result are valid pointers and buf.len() is the size of
+    // buf
+    let ret = unsafe {
+        libc::getgrgid_r(
+            gid,
+            group.as_mut_ptr(),
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if ret != 0 || result.is_null() {
+        return None;
+    }
+    // SAFETY: libc call was success, gr_name points to a nul terminated string in buf
+    let name = unsafe { std::ffi::CStr::from_ptr((*result).gr_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_numa_maps() {
+        let content = "\
+558a58427000 default file=/usr/bin/head mapped=2 N0=2 kernelpagesize_kB=4
+7f4c2c000000 default anon=3 dirty=3 N0=1 N1=2 kernelpagesize_kB=4
+7f4c40000000 bind:1 anon=1 dirty=1 N1=1 kernelpagesize_kB=2048
+7ffd5b7fe000 default
+";
+        let nodes = parse_numa_maps(content);
+        assert_eq!(nodes.get(&0), Some(&(3 * 4096)));
+        assert_eq!(nodes.get(&1), Some(&(2 * 4096 + 2048 * 1024)));
+        assert_eq!(nodes.len(), 2);
+    }
+
+    #[test]
+    fn test_cpu_affinity() {
+        let cpus = cpu_affinity().unwrap();
+        let parallelism = std::thread::available_parallelism().unwrap().get() as u64;
+        // available_parallelism may be lowered further by cgroup quotas
+        assert!(cpus >= parallelism, "{} < {}", cpus, parallelism);
     }
-    metrics
 }
@@ -8,7 +8,9 @@ use rlimit::{getrlimit, Resource};
 use std::mem::MaybeUninit;
 use std::process;
 
-use super::Metrics;
+use super::{
+    Error, Errors, Field, MallocStats, MetricSet, Metrics, NumaMemory, ProcessInfo, ThreadStates,
+};
 
 // https://stackoverflow.com/a/72915413
 // https://openradar.appspot.com/FB9546856
@@ -22,26 +24,103 @@ static TIMEBASE_TO_NANOSECONDS: Lazy<f64> = Lazy::new(|| {
     info.numer as f64 / info.denom as f64
 });
 
-pub fn collect() -> Metrics {
+// Fields read from pidrusage()
+const CPU_FIELDS: &[Field] = &[
+    Field::CpuSecondsTotal,
+    Field::CpuUserSecondsTotal,
+    Field::CpuSystemSecondsTotal,
+];
+
+// Fields read from pidinfo::<TaskAllInfo>()
+const TASK_ALL_INFO_FIELDS: &[Field] = &[
+    Field::StartTimeSeconds,
+    Field::VirtualMemoryBytes,
+    Field::ResidentMemoryBytes,
+    Field::Threads,
+    Field::OpenFds,
+];
+
+// Metrics read from pidinfo::<TaskAllInfo>()
+const TASK_ALL_INFO_SET: MetricSet = MetricSet::START_TIME
+    .union(MetricSet::MEMORY)
+    .union(MetricSet::THREADS)
+    .union(MetricSet::FDS);
+
+// Fields that are not supported on macOS
+pub const UNSUPPORTED_FIELDS: &[Field] = &[
+    Field::IoWaitSecondsTotal,
+    Field::Nice,
+    Field::Priority,
+    Field::SchedPolicy,
+    Field::RtPriority,
+    Field::CpuAffinity,
+];
+
+pub fn try_collect(set: MetricSet) -> (Metrics, Errors) {
     let pid = process::id() as i32;
     let mut metrics = Metrics::default();
-    if let Ok(res) = pidrusage::<RUsageInfoV2>(pid) {
-        metrics.cpu_seconds_total = {
-            let t = res.ri_user_time + res.ri_system_time;
-            let t = t as f64 * *TIMEBASE_TO_NANOSECONDS / 1e9;
-            Some(t)
-        };
+    let mut errors = Errors::default();
+    if set.contains(MetricSet::CPU) {
+        match pidrusage::<RUsageInfoV2>(pid) {
+            Ok(res) => {
+                metrics.cpu_seconds_total = {
+                    let t = res.ri_user_time + res.ri_system_time;
+                    let t = t as f64 * *TIMEBASE_TO_NANOSECONDS / 1e9;
+                    Some(t)
+                };
+                metrics.cpu_user_seconds_total =
+                    Some(res.ri_user_time as f64 * *TIMEBASE_TO_NANOSECONDS / 1e9);
+                metrics.cpu_system_seconds_total =
+                    Some(res.ri_system_time as f64 * *TIMEBASE_TO_NANOSECONDS / 1e9);
+            }
+            Err(e) => errors.insert_all(CPU_FIELDS, Error::Other(e.into())),
+        }
     }
-    if let Ok(info) = pidinfo::<TaskAllInfo>(pid, 0) {
-        metrics.start_time_seconds = Some(info.pbsd.pbi_start_tvsec);
-        metrics.virtual_memory_bytes = Some(info.ptinfo.pti_virtual_size);
-        metrics.resident_memory_bytes = Some(info.ptinfo.pti_resident_size);
-        metrics.threads = Some(info.ptinfo.pti_threadnum as u64);
-        metrics.open_fds = listpidinfo::<ListFDs>(pid, info.pbsd.pbi_nfiles as usize)
-            .ok()
-            .map(|v| v.len() as u64);
+    if set.intersects(TASK_ALL_INFO_SET) {
+        match pidinfo::<TaskAllInfo>(pid, 0) {
+            Ok(info) => {
+                metrics.start_time_seconds = Some(info.pbsd.pbi_start_tvsec);
+                metrics.virtual_memory_bytes = Some(info.ptinfo.pti_virtual_size);
+                metrics.resident_memory_bytes = Some(info.ptinfo.pti_resident_size);
+                metrics.threads = Some(info.ptinfo.pti_threadnum as u64);
+                if set.contains(MetricSet::FDS) {
+                    match listpidinfo::<ListFDs>(pid, info.pbsd.pbi_nfiles as usize) {
+                        Ok(v) => metrics.open_fds = Some(v.len() as u64),
+                        Err(e) => errors.insert(Field::OpenFds, Error::Other(e.into())),
+                    }
+                }
+            }
+            Err(e) => errors.insert_all(TASK_ALL_INFO_FIELDS, Error::Other(e.into())),
+        }
     }
-    metrics.virtual_memory_max_bytes = getrlimit(Resource::AS).ok().map(|(soft, _hard)| soft);
-    metrics.max_fds = getrlimit(Resource::NOFILE).ok().map(|(soft, _hard)| soft);
-    metrics
+    if set.contains(MetricSet::MEMORY) {
+        match getrlimit(Resource::AS) {
+            Ok((soft, _hard)) => metrics.virtual_memory_max_bytes = Some(soft),
+            Err(e) => errors.insert(Field::VirtualMemoryMaxBytes, e.into()),
+        }
+    }
+    if set.contains(MetricSet::FDS) {
+        match getrlimit(Resource::NOFILE) {
+            Ok((soft, _hard)) => metrics.max_fds = Some(soft),
+            Err(e) => errors.insert(Field::MaxFds, e.into()),
+        }
+    }
+    errors.insert_all(UNSUPPORTED_FIELDS, Error::Unsupported);
+    (metrics, errors)
+}
+
+pub fn collect_thread_states() -> Option<ThreadStates> {
+    None
+}
+
+pub fn collect_numa_memory() -> Option<NumaMemory> {
+    None
+}
+
+pub fn collect_malloc_stats() -> Option<MallocStats> {
+    None
+}
+
+pub fn collect_process_info() -> Option<ProcessInfo> {
+    None
 }
@@ -1,26 +1,29 @@
 use std::convert::TryInto as _;
+use std::io;
 
-use super::Metrics;
+use super::{
+    Error, Errors, Field, MallocStats, MetricSet, Metrics, NumaMemory, ProcessInfo, ThreadStates,
+};
 
-fn getrusage(who: libc::c_int) -> Option<libc::rusage> {
+fn getrusage(who: libc::c_int) -> io::Result<libc::rusage> {
     let mut usage = std::mem::MaybeUninit::zeroed();
     // SAFETY: libc call; usage is valid pointer to rusage struct
     if unsafe { libc::getrusage(who, usage.as_mut_ptr()) } == 0 {
         // SAFETY: libc call was success, struct must be initialized
-        Some(unsafe { usage.assume_init() })
+        Ok(unsafe { usage.assume_init() })
     } else {
-        None
+        Err(io::Error::last_os_error())
     }
 }
 
-fn getrlimit(resource: libc::c_int) -> Option<libc::rlimit> {
+fn getrlimit(resource: libc::c_int) -> io::Result<libc::rlimit> {
     let mut limit = std::mem::MaybeUninit::zeroed();
     // SAFETY: libc call; limit is valid pointer to rlimit struct
     if unsafe { libc::getrlimit(resource, limit.as_mut_ptr()) } == 0 {
         // SAFETY: libc call was success, struct must be initialized
-        Some(unsafe { limit.assume_init() })
+        Ok(unsafe { limit.assume_init() })
     } else {
-        None
+        Err(io::Error::last_os_error())
     }
 }
 
@@ -32,7 +35,7 @@ fn translate_rlim(rlim: libc::rlim_t) -> u64 {
     }
 }
 
-fn kinfo_getproc(pid: libc::pid_t) -> Option<libc::kinfo_proc> {
+fn kinfo_getproc(pid: libc::pid_t) -> io::Result<libc::kinfo_proc> {
     let mut kinfo_proc = std::mem::MaybeUninit::zeroed();
     let kinfo_proc_size = std::mem::size_of_val(&kinfo_proc) as libc::size_t;
     let mut data_size = kinfo_proc_size;
@@ -51,7 +54,7 @@ fn kinfo_getproc(pid: libc::pid_t) -> Option<libc::kinfo_proc> {
 
     // SAFETY: libc call; mib is statically initialized, kinfo_proc is valid pointer
     // to kinfo_proc and data_size holds its size
-    if unsafe {
+    let ret = unsafe {
         libc::sysctl(
             mib.as_ptr(),
             mib.len() as _,
@@ -60,18 +63,47 @@ fn kinfo_getproc(pid: libc::pid_t) -> Option<libc::kinfo_proc> {
             std::ptr::null_mut(),
             0,
         )
-    } == 0
-        && data_size == kinfo_proc_size
-    {
-        // SAFETY: libc call was success and check for struct size passed, struct must be initialized
-        Some(unsafe { kinfo_proc.assume_init() })
+    };
+    if ret != 0 {
+        Err(io::Error::last_os_error())
+    } else if data_size != kinfo_proc_size {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unexpected size of kinfo_proc",
+        ))
     } else {
-        None
+        // SAFETY: libc call was success and check for struct size passed, struct must be initialized
+        Ok(unsafe { kinfo_proc.assume_init() })
     }
 }
 
-pub fn collect() -> Metrics {
+// Fields read from getrusage()
+const CPU_FIELDS: &[Field] = &[
+    Field::CpuSecondsTotal,
+    Field::CpuUserSecondsTotal,
+    Field::CpuSystemSecondsTotal,
+];
+
+// Fields read from kinfo_proc
+const KINFO_PROC_FIELDS: &[Field] = &[Field::ResidentMemoryBytes, Field::StartTimeSeconds];
+
+// Fields that are not supported on OpenBSD
+pub const UNSUPPORTED_FIELDS: &[Field] = &[
+    Field::OpenFds,
+    Field::VirtualMemoryBytes,
+    Field::VirtualMemoryMaxBytes,
+    Field::Threads,
+    Field::IoWaitSecondsTotal,
+    Field::Nice,
+    Field::Priority,
+    Field::SchedPolicy,
+    Field::RtPriority,
+    Field::CpuAffinity,
+];
+
+pub fn try_collect(set: MetricSet) -> (Metrics, Errors) {
     let mut metrics = Metrics::default();
+    let mut errors = Errors::default();
 
     // TODO: this is based on freebsd.rs, but lacks
     // - virtual_memory_bytes (kinfo_proc::p_vm_map_size contains zero)
@@ -79,29 +111,63 @@ pub fn collect() -> Metrics {
     // - threads (no corresponding field in kinfo_proc(
     // - open_fds (no idea where to get it from)
 
-    if let Some(usage) = getrusage(libc::RUSAGE_SELF) {
-        metrics.cpu_seconds_total = Some(
-            (usage.ru_utime.tv_sec + usage.ru_stime.tv_sec) as f64
-                + (usage.ru_utime.tv_usec + usage.ru_stime.tv_usec) as f64 / 1000000.0,
-        );
+    if set.contains(MetricSet::CPU) {
+        match getrusage(libc::RUSAGE_SELF) {
+            Ok(usage) => {
+                metrics.cpu_seconds_total = Some(
+                    (usage.ru_utime.tv_sec + usage.ru_stime.tv_sec) as f64
+                        + (usage.ru_utime.tv_usec + usage.ru_stime.tv_usec) as f64 / 1000000.0,
+                );
+                metrics.cpu_user_seconds_total =
+                    Some(usage.ru_utime.tv_sec as f64 + usage.ru_utime.tv_usec as f64 / 1000000.0);
+                metrics.cpu_system_seconds_total =
+                    Some(usage.ru_stime.tv_sec as f64 + usage.ru_stime.tv_usec as f64 / 1000000.0);
+            }
+            Err(e) => errors.insert_all(CPU_FIELDS, e.into()),
+        }
     }
 
-    if let Some(limit_as) = getrlimit(libc::RLIMIT_NOFILE) {
-        metrics.max_fds = Some(translate_rlim(limit_as.rlim_cur));
+    if set.contains(MetricSet::FDS) {
+        match getrlimit(libc::RLIMIT_NOFILE) {
+            Ok(limit_as) => metrics.max_fds = Some(translate_rlim(limit_as.rlim_cur)),
+            Err(e) => errors.insert(Field::MaxFds, e.into()),
+        }
     }
 
     // SAFETY: libc call
     let pid = unsafe { libc::getpid() };
 
-    if let Some(kinfo_proc) = kinfo_getproc(pid) {
-        // reference:
-        // https://github.com/openbsd/src/blob/782feb691bc15d1abd5f5c66fe3c0d336903a461/sys/sys/sysctl.h#L370
+    if set.intersects(MetricSet::MEMORY.union(MetricSet::START_TIME)) {
+        match kinfo_getproc(pid) {
+            Ok(kinfo_proc) => {
+                // reference:
+                // https://github.com/openbsd/src/blob/782feb691bc15d1abd5f5c66fe3c0d336903a461/sys/sys/sysctl.h#L370
 
-        // SAFETY: libc call
-        let pagesize = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
-        metrics.resident_memory_bytes = Some(kinfo_proc.p_vm_rssize as u64 * pagesize);
-        metrics.start_time_seconds = Some(kinfo_proc.p_ustart_sec);
+                // SAFETY: libc call
+                let pagesize = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+                metrics.resident_memory_bytes = Some(kinfo_proc.p_vm_rssize as u64 * pagesize);
+                metrics.start_time_seconds = Some(kinfo_proc.p_ustart_sec);
+            }
+            Err(e) => errors.insert_all(KINFO_PROC_FIELDS, e.into()),
+        }
     }
 
-    metrics
+    errors.insert_all(UNSUPPORTED_FIELDS, Error::Unsupported);
+    (metrics, errors)
+}
+
+pub fn collect_thread_states() -> Option<ThreadStates> {
+    None
+}
+
+pub fn collect_numa_memory() -> Option<NumaMemory> {
+    None
+}
+
+pub fn collect_malloc_stats() -> Option<MallocStats> {
+    None
+}
+
+pub fn collect_process_info() -> Option<ProcessInfo> {
+    None
 }
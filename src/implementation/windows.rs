@@ -1,7 +1,9 @@
 use std::mem::{size_of, MaybeUninit};
 use windows::{Win32::Foundation::*, Win32::System::ProcessStatus::*, Win32::System::Threading::*};
 
-use super::Metrics;
+use super::{
+    Error, Errors, Field, MallocStats, MetricSet, Metrics, NumaMemory, ProcessInfo, ThreadStates,
+};
 
 /// Collect metrics.
 ///
@@ -19,11 +21,12 @@ use super::Metrics;
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-pub fn collect() -> Metrics {
+pub fn try_collect(set: MetricSet) -> (Metrics, Errors) {
     let mut metrics = Metrics::default();
+    let mut errors = Errors::default();
     unsafe {
         let h = GetCurrentProcess();
-        let (start_time_seconds, cpu_seconds_total) = {
+        if set.intersects(MetricSet::CPU.union(MetricSet::START_TIME)) {
             let mut creationtime = MaybeUninit::uninit();
             let mut _exittime = MaybeUninit::uninit();
             let mut kerneltime = MaybeUninit::uninit();
@@ -35,26 +38,36 @@ pub fn collect() -> Metrics {
                 kerneltime.as_mut_ptr(),
                 usertime.as_mut_ptr(),
             );
-            if ret.is_ok() {
-                // `creationtime` and `_exittime` are points in time expressed as the amount of time that
-                // has elapsed since midnight on January 1, 1601 in 100 nanosecond time units.
-                let start_time_seconds =
-                    filetime_to_unix_epoch_in_seconds(creationtime.assume_init());
-                // `kerneltime` and `usertime` are amounts of time in 100 nanosecond time units.
-                let cpu_seconds_total = {
-                    let stime = filetime_to_seconds(kerneltime.assume_init());
-                    let utime = filetime_to_seconds(usertime.assume_init());
-                    stime + utime
-                };
-                (Some(start_time_seconds as u64), Some(cpu_seconds_total))
-            } else {
-                (None, None)
+            match ret {
+                Ok(()) => {
+                    // `creationtime` and `_exittime` are points in time expressed as the amount of time that
+                    // has elapsed since midnight on January 1, 1601 in 100 nanosecond time units.
+                    let start_time_seconds =
+                        filetime_to_unix_epoch_in_seconds(creationtime.assume_init());
+                    // `kerneltime` and `usertime` are amounts of time in 100 nanosecond time units.
+                    let cpu_seconds_total = {
+                        let stime = filetime_to_seconds(kerneltime.assume_init());
+                        let utime = filetime_to_seconds(usertime.assume_init());
+                        stime + utime
+                    };
+                    metrics.start_time_seconds = Some(start_time_seconds as u64);
+                    metrics.cpu_seconds_total = Some(cpu_seconds_total);
+                    metrics.cpu_user_seconds_total =
+                        Some(filetime_to_seconds(usertime.assume_init()));
+                    metrics.cpu_system_seconds_total =
+                        Some(filetime_to_seconds(kerneltime.assume_init()));
+                }
+                Err(e) => {
+                    let e = Error::from(std::io::Error::from(e));
+                    errors.insert(Field::StartTimeSeconds, e.clone());
+                    errors.insert(Field::CpuSecondsTotal, e.clone());
+                    errors.insert(Field::CpuUserSecondsTotal, e.clone());
+                    errors.insert(Field::CpuSystemSecondsTotal, e);
+                }
             }
-        };
-        metrics.start_time_seconds = start_time_seconds;
-        metrics.cpu_seconds_total = cpu_seconds_total;
+        }
 
-        let (virtual_memory_bytes, resident_memory_bytes) = {
+        if set.contains(MetricSet::MEMORY) {
             // We need to use PROCESS_MEMORY_COUNTERS_EX but GetProcessMemoryInfoEx is not provided
             // thus we need to cast PROCESS_MEMORY_COUNTERS_EX into PROCESS_MEMORY_COUNTERS to use
             // it with GetProcessMemoryInfo.
@@ -64,35 +77,47 @@ pub fn collect() -> Metrics {
             };
             let cb = size_of::<PROCESS_MEMORY_COUNTERS_EX>();
             let ret = GetProcessMemoryInfo(h, memcounters, cb as u32);
-            if ret.is_ok() {
-                let memcounters = memcounters as *const _ as *const PROCESS_MEMORY_COUNTERS_EX;
-                let &memcounters = &*memcounters;
-                (
-                    Some(memcounters.PrivateUsage as u64),
-                    Some(memcounters.WorkingSetSize as u64),
-                )
-            } else {
-                (None, None)
+            match ret {
+                Ok(()) => {
+                    let memcounters = memcounters as *const _ as *const PROCESS_MEMORY_COUNTERS_EX;
+                    let &memcounters = &*memcounters;
+                    metrics.virtual_memory_bytes = Some(memcounters.PrivateUsage as u64);
+                    metrics.resident_memory_bytes = Some(memcounters.WorkingSetSize as u64);
+                }
+                Err(e) => {
+                    let e = Error::from(std::io::Error::from(e));
+                    errors.insert(Field::VirtualMemoryBytes, e.clone());
+                    errors.insert(Field::ResidentMemoryBytes, e);
+                }
             }
-        };
-        metrics.virtual_memory_bytes = virtual_memory_bytes;
-        metrics.resident_memory_bytes = resident_memory_bytes;
+        }
 
-        let open_fds = {
+        if set.contains(MetricSet::FDS) {
             let mut handlecount = 0;
             let ret = GetProcessHandleCount(h, &mut handlecount);
-            if ret.is_ok() {
-                Some(handlecount as u64)
-            } else {
-                None
+            match ret {
+                Ok(()) => metrics.open_fds = Some(handlecount as u64),
+                Err(e) => errors.insert(Field::OpenFds, std::io::Error::from(e).into()),
             }
-        };
-        metrics.open_fds = open_fds;
+        }
         metrics.max_fds = Some(16 * 1024 * 1024); // Windows has a hard-coded max limit, not per-process.
     }
-    metrics
+    errors.insert_all(UNSUPPORTED_FIELDS, Error::Unsupported);
+    (metrics, errors)
 }
 
+// Fields that are not supported on Windows
+pub const UNSUPPORTED_FIELDS: &[Field] = &[
+    Field::VirtualMemoryMaxBytes,
+    Field::Threads,
+    Field::IoWaitSecondsTotal,
+    Field::Nice,
+    Field::Priority,
+    Field::SchedPolicy,
+    Field::RtPriority,
+    Field::CpuAffinity,
+];
+
 /// Convert FILETIME to seconds.
 ///
 /// FILETIME contains a 64-bit value representing the number of 100-nanosecond intervals.
@@ -156,3 +181,19 @@ fn filetime_to_unix_epoch_in_seconds(ft: FILETIME) -> f64 {
     // convert into seconds
     nsec as f64 / 1e9
 }
+
+pub fn collect_thread_states() -> Option<ThreadStates> {
+    None
+}
+
+pub fn collect_numa_memory() -> Option<NumaMemory> {
+    None
+}
+
+pub fn collect_malloc_stats() -> Option<MallocStats> {
+    None
+}
+
+pub fn collect_process_info() -> Option<ProcessInfo> {
+    None
+}
@@ -0,0 +1,522 @@
+//! Write process metrics in the [InfluxDB line protocol].
+//!
+//! This module provides [`Encoder`], which turns a snapshot of [`Metrics`] into a line of a
+//! single `process` measurement with a field per metric, and [`InfluxWriter`], which writes the
+//! lines periodically to a TCP or UDP socket (e.g. the socket listener input of Telegraf) or to a
+//! file.
+//!
+//! Fields have the names of the fields of [`Metrics`], e.g. `cpu_seconds_total` and `open_fds`.
+//! Counts and sizes are integers, times in seconds are floats, and `sched_policy` is a string.
+//! Metrics that are `None` are omitted.
+//!
+//! [InfluxDB line protocol]: https://docs.influxdata.com/influxdb/v2/reference/syntax/line-protocol/
+
+use std::fmt::{self, Write as _};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::collector::{self, MetricSet, Metrics};
+use crate::periodic::{self, PeriodicHandle};
+
+const DEFAULT_TCP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An encoder of [`Metrics`] into the InfluxDB line protocol.
+///
+/// # Examples
+///
+/// ```
+/// # use std::time::SystemTime;
+/// # use metrics_process::collector::collect;
+/// # use metrics_process::influxdb::Encoder;
+/// let encoder = Encoder::new().tags([("service", "api")]);
+/// let line = encoder.encode(&collect(), SystemTime::now());
+/// assert!(line.starts_with("process,"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    measurement: String,
+    tags: Vec<(String, String)>,
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Encoder {
+    /// Create a new encoder of the `process` measurement with the `host` and `pid` tags.
+    ///
+    /// The `host` tag is omitted when the hostname cannot be determined.
+    pub fn new() -> Self {
+        let mut tags = Vec::new();
+        if let Some(host) = hostname() {
+            tags.push(("host".to_string(), host));
+        }
+        tags.push(("pid".to_string(), process::id().to_string()));
+        Self {
+            measurement: "process".to_string(),
+            tags,
+        }
+    }
+
+    /// Set the name of the measurement. Defaults to `process`.
+    pub fn measurement(mut self, measurement: impl Into<String>) -> Self {
+        self.measurement = measurement.into();
+        self
+    }
+
+    /// Add tags to the line, replacing those with the same keys (e.g. `host`).
+    ///
+    /// The line protocol does not allow empty tag keys or values, so such tags are not added.
+    /// A tag with an empty value removes the tag with the same key.
+    pub fn tags<K, V>(mut self, tags: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        for (key, value) in tags {
+            let (key, value) = (key.into(), value.into());
+            self.tags.retain(|(k, _)| *k != key);
+            if !key.is_empty() && !value.is_empty() {
+                self.tags.push((key, value));
+            }
+        }
+        self
+    }
+
+    /// Encode the metrics into a line terminated by a newline, with the given timestamp in
+    /// nanoseconds.
+    ///
+    /// Returns an empty string when no metric is available, since a line requires at least one
+    /// field.
+    pub fn encode(&self, metrics: &Metrics, timestamp: SystemTime) -> String {
+        let mut fields = String::new();
+        let mut push = |key: &str, value: FieldValue| {
+            if !fields.is_empty() {
+                fields.push(',');
+            }
+            let _ = write!(fields, "{key}={value}");
+        };
+        if let Some(v) = metrics.cpu_seconds_total {
+            push("cpu_seconds_total", FieldValue::Float(v));
+        }
+        if let Some(v) = metrics.cpu_user_seconds_total {
+            push("cpu_user_seconds_total", FieldValue::Float(v));
+        }
+        if let Some(v) = metrics.cpu_system_seconds_total {
+            push("cpu_system_seconds_total", FieldValue::Float(v));
+        }
+        if let Some(v) = metrics.open_fds {
+            push("open_fds", FieldValue::Int(v as i64));
+        }
+        if let Some(v) = metrics.max_fds {
+            push("max_fds", FieldValue::Int(v as i64));
+        }
+        if let Some(v) = metrics.virtual_memory_bytes {
+            push("virtual_memory_bytes", FieldValue::Int(v as i64));
+        }
+        if let Some(v) = metrics.virtual_memory_max_bytes {
+            push("virtual_memory_max_bytes", FieldValue::Int(v as i64));
+        }
+        if let Some(v) = metrics.resident_memory_bytes {
+            push("resident_memory_bytes", FieldValue::Int(v as i64));
+        }
+        if let Some(v) = metrics.heap_bytes {
+            push("heap_bytes", FieldValue::Int(v as i64));
+        }
+        if let Some(v) = metrics.start_time_seconds {
+            push("start_time_seconds", FieldValue::Int(v as i64));
+        }
+        if let Some(v) = metrics.threads {
+            push("threads", FieldValue::Int(v as i64));
+        }
+        if let Some(v) = metrics.io_wait_seconds_total {
+            push("io_wait_seconds_total", FieldValue::Float(v));
+        }
+        if let Some(v) = metrics.nice {
+            push("nice", FieldValue::Int(v));
+        }
+        if let Some(v) = metrics.priority {
+            push("priority", FieldValue::Int(v));
+        }
+        if let Some(v) = metrics.sched_policy {
+            push("sched_policy", FieldValue::Str(v.as_str()));
+        }
+        if let Some(v) = metrics.rt_priority {
+            push("rt_priority", FieldValue::Int(v as i64));
+        }
+        if let Some(v) = metrics.cpu_affinity {
+            push("cpu_affinity", FieldValue::Int(v as i64));
+        }
+        if fields.is_empty() {
+            return String::new();
+        }
+
+        let mut line = escape(&self.measurement, &[',', ' ']);
+        let mut tags: Vec<_> = self.tags.iter().collect();
+        // InfluxDB recommends sorting tags by key for the best performance
+        tags.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (key, value) in tags {
+            let _ = write!(
+                line,
+                ",{}={}",
+                escape(key, &[',', '=', ' ']),
+                escape(value, &[',', '=', ' '])
+            );
+        }
+        let timestamp = timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let _ = writeln!(line, " {fields} {timestamp}");
+        line
+    }
+}
+
+enum FieldValue {
+    Float(f64),
+    Int(i64),
+    Str(&'static str),
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // `{:?}` keeps the fraction of integral values, e.g. `1.0`, so it is obvious that
+            // the field is a float
+            FieldValue::Float(v) => write!(f, "{v:?}"),
+            FieldValue::Int(v) => write!(f, "{v}i"),
+            FieldValue::Str(v) => write!(f, "\"{}\"", escape(v, &['"', '\\'])),
+        }
+    }
+}
+
+fn escape(s: &str, chars: &[char]) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if chars.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "openbsd"
+))]
+fn hostname() -> Option<String> {
+    // Host names are at most 255 bytes (HOST_NAME_MAX is 64 on Linux) plus the terminating NUL
+    let mut buf = [0u8; 256];
+    // SAFETY: libc call; buf is a valid writable buffer of buf.len() bytes. gethostname writes
+    // at most that many bytes but may omit the NUL when truncating, so the name is read up to
+    // the first NUL or the end of buf below rather than as a C string
+    if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8(buf[..len].to_vec())
+        .ok()
+        .filter(|s| !s.is_empty())
+}
+
+#[cfg(not(any(
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "openbsd"
+)))]
+fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .ok()
+}
+
+#[derive(Debug)]
+enum Target {
+    Tcp {
+        addrs: Vec<SocketAddr>,
+        timeout: Duration,
+        stream: Option<TcpStream>,
+    },
+    Udp(UdpSocket),
+    File(File),
+}
+
+// Connect to the first of the addresses that accepts a connection within the timeout. The
+// timeout also applies to writes, which fail once the receiver stops reading.
+fn connect_tcp(addrs: &[SocketAddr], timeout: Duration) -> io::Result<TcpStream> {
+    let mut error = io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to");
+    for addr in addrs {
+        match TcpStream::connect_timeout(addr, timeout) {
+            Ok(stream) => {
+                stream.set_write_timeout(Some(timeout))?;
+                return Ok(stream);
+            }
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+/// A writer of the metrics in the InfluxDB line protocol to a socket or a file.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use metrics_process::influxdb::{Encoder, InfluxWriter};
+/// let handle = InfluxWriter::udp("127.0.0.1:8094")?
+///     .encoder(Encoder::new().tags([("service", "api")]))
+///     .spawn(Duration::from_secs(10))?;
+/// // A line is written every 10 seconds until the handle is dropped
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct InfluxWriter {
+    target: Target,
+    encoder: Encoder,
+    metric_set: MetricSet,
+}
+
+impl InfluxWriter {
+    /// Create a new writer that writes to a TCP connection to the given address.
+    ///
+    /// The connection is established on the first write, and re-established on the next write
+    /// after it fails. Connecting and writing fail after the
+    /// [`tcp_timeout`](Self::tcp_timeout), so a receiver that is unreachable or stops reading
+    /// does not block the writer.
+    pub fn tcp(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let addrs = addr.to_socket_addrs()?.collect();
+        Ok(Self::new(Target::Tcp {
+            addrs,
+            timeout: DEFAULT_TCP_TIMEOUT,
+            stream: None,
+        }))
+    }
+
+    /// Create a new writer that sends a datagram to the given UDP address for each line.
+    pub fn udp(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let socket = periodic::connect_udp(addr)?;
+        Ok(Self::new(Target::Udp(socket)))
+    }
+
+    /// Create a new writer that appends to the file at the given path, creating it if it does
+    /// not exist.
+    pub fn file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(Target::File(file)))
+    }
+
+    fn new(target: Target) -> Self {
+        Self {
+            target,
+            encoder: Encoder::new(),
+            metric_set: MetricSet::default(),
+        }
+    }
+
+    /// Set the encoder of the lines. Defaults to [`Encoder::new()`].
+    pub fn encoder(mut self, encoder: Encoder) -> Self {
+        self.encoder = encoder;
+        self
+    }
+
+    /// Set the timeout of connecting and writing to a TCP connection. Defaults to 5 seconds.
+    ///
+    /// It has no effect on the other targets.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timeout is zero.
+    pub fn tcp_timeout(mut self, timeout: Duration) -> Self {
+        assert!(!timeout.is_zero(), "timeout must not be zero");
+        if let Target::Tcp { timeout: t, .. } = &mut self.target {
+            *t = timeout;
+        }
+        self
+    }
+
+    /// Select the metrics to collect in [`spawn()`](Self::spawn). Defaults to
+    /// [`MetricSet::default()`].
+    pub fn metrics(mut self, set: MetricSet) -> Self {
+        self.metric_set = set;
+        self
+    }
+
+    /// Write the metrics in the snapshot with the current time as the timestamp.
+    pub fn write(&mut self, metrics: &Metrics) -> io::Result<()> {
+        let line = self.encoder.encode(metrics, SystemTime::now());
+        if line.is_empty() {
+            return Ok(());
+        }
+        match &mut self.target {
+            Target::Tcp {
+                addrs,
+                timeout,
+                stream,
+            } => {
+                let result = match stream {
+                    Some(stream) => stream.write_all(line.as_bytes()),
+                    None => connect_tcp(addrs, *timeout).and_then(|mut s| {
+                        s.write_all(line.as_bytes())?;
+                        *stream = Some(s);
+                        Ok(())
+                    }),
+                };
+                if result.is_err() {
+                    *stream = None;
+                }
+                result
+            }
+            Target::Udp(socket) => socket.send(line.as_bytes()).map(|_| ()),
+            Target::File(file) => file.write_all(line.as_bytes()),
+        }
+    }
+
+    /// Start a background thread named `metrics-process-influxdb` that collects the selected
+    /// metrics and writes them every interval.
+    ///
+    /// Errors of writing are ignored as described in [`PeriodicHandle`]. The thread stops when
+    /// the returned handle is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval is zero.
+    pub fn spawn(mut self, interval: Duration) -> io::Result<PeriodicHandle> {
+        periodic::spawn("metrics-process-influxdb", interval, move || {
+            let metrics = collector::collect_selected(self.metric_set);
+            let _ = self.write(&metrics);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collector::SchedPolicy;
+    use assert_matches::assert_matches;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    fn metrics() -> Metrics {
+        Metrics {
+            cpu_seconds_total: Some(1.0),
+            open_fds: Some(8),
+            nice: Some(-5),
+            sched_policy: Some(SchedPolicy::Other),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        let encoder = Encoder::new().tags([("host", "my host"), ("service", "a,b=c")]);
+        let timestamp = UNIX_EPOCH + Duration::new(1_700_000_000, 123);
+        assert_eq!(
+            encoder.encode(&metrics(), timestamp),
+            format!(
+                concat!(
+                    "process,host=my\\ host,pid={},service=a\\,b\\=c ",
+                    "cpu_seconds_total=1.0,open_fds=8i,nice=-5i,sched_policy=\"SCHED_OTHER\" ",
+                    "1700000000000000123\n",
+                ),
+                process::id()
+            )
+        );
+        assert_eq!(encoder.encode(&Metrics::default(), timestamp), "");
+    }
+
+    #[test]
+    fn test_encode_empty_tags() {
+        let encoder = Encoder::new().tags([("host", ""), ("", "api"), ("service", "")]);
+        let line = encoder.encode(&metrics(), UNIX_EPOCH);
+        assert!(
+            line.starts_with(&format!("process,pid={} ", process::id())),
+            "{}",
+            line
+        );
+    }
+
+    #[test]
+    fn test_write_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut writer = InfluxWriter::tcp(listener.local_addr().unwrap()).unwrap();
+        writer.write(&metrics()).unwrap();
+        writer.write(&metrics()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let lines: Vec<_> = BufReader::new(stream)
+            .lines()
+            .take(2)
+            .map(|l| l.unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines
+            .iter()
+            .all(|l| l.starts_with("process,") && l.contains(" cpu_seconds_total=1.0,")));
+    }
+
+    #[test]
+    fn test_write_tcp_not_reading() {
+        // The receiver accepts the connection but never reads, so the socket buffers fill up
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut writer = InfluxWriter::tcp(listener.local_addr().unwrap())
+            .unwrap()
+            .tcp_timeout(Duration::from_millis(100))
+            .encoder(Encoder::new().tags([("padding", "x".repeat(64 << 10))]));
+        let start = std::time::Instant::now();
+        let error = loop {
+            if let Err(e) = writer.write(&metrics()) {
+                break e;
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(30),
+                "write never failed"
+            );
+        };
+        assert!(
+            matches!(
+                error.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ),
+            "{:?}",
+            error
+        );
+        assert_matches!(writer.target, Target::Tcp { stream: None, .. });
+    }
+
+    #[test]
+    fn test_write_udp() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut writer = InfluxWriter::udp(listener.local_addr().unwrap())
+            .unwrap()
+            .encoder(Encoder::new().measurement("app process"));
+        writer.write(&metrics()).unwrap();
+        let mut buf = [0; 1024];
+        let n = listener.recv(&mut buf).unwrap();
+        let line = std::str::from_utf8(&buf[..n]).unwrap();
+        assert!(line.starts_with("app\\ process,"), "{}", line);
+        assert!(line.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_write_file() {
+        let path =
+            std::env::temp_dir().join(format!("metrics-process-influxdb-{}.txt", process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut writer = InfluxWriter::file(&path).unwrap();
+        writer.write(&metrics()).unwrap();
+        writer.write(&metrics()).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+    }
+}
@@ -0,0 +1,92 @@
+//! jemalloc statistics.
+//!
+//! This module reads the statistics of [jemalloc] through its `mallctl` interface with
+//! [tikv-jemalloc-ctl]. It is meant to be used with [tikv-jemallocator] registered as the global
+//! allocator, otherwise it only reports the (almost empty) heap of jemalloc itself.
+//!
+//! Once the `jemalloc` feature is enabled, `Collector` exports the statistics as
+//! `process_heap_*` gauges.
+//!
+//! # Examples
+//!
+//! ```
+//! let stats = metrics_process::jemalloc::stats().unwrap();
+//! assert!(stats.resident_bytes >= stats.active_bytes);
+//! ```
+//!
+//! [jemalloc]: https://jemalloc.net/
+//! [tikv-jemalloc-ctl]: https://crates.io/crates/tikv-jemalloc-ctl
+//! [tikv-jemallocator]: https://crates.io/crates/tikv-jemallocator
+
+use tikv_jemalloc_ctl::{epoch, stats};
+
+/// Heap statistics of jemalloc.
+///
+/// See <https://jemalloc.net/jemalloc.3.html> for the details of each statistic.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JemallocStats {
+    /// Total number of bytes allocated by the application (`stats.allocated`).
+    pub allocated_bytes: u64,
+    /// Total number of bytes in active pages allocated by the application (`stats.active`).
+    pub active_bytes: u64,
+    /// Total number of bytes in physically resident data pages mapped by the allocator
+    /// (`stats.resident`).
+    pub resident_bytes: u64,
+    /// Total number of bytes in virtual memory mappings that were retained rather than being
+    /// returned to the operating system (`stats.retained`).
+    pub retained_bytes: u64,
+    /// Total number of bytes dedicated to metadata (`stats.metadata`).
+    pub metadata_bytes: u64,
+}
+
+/// Return the heap statistics of jemalloc.
+///
+/// jemalloc caches its statistics, so this advances the `epoch` to refresh them first. Returns
+/// `None` when any of the statistics cannot be read, e.g. jemalloc is built without
+/// `--enable-stats`.
+pub fn stats() -> Option<JemallocStats> {
+    epoch::advance().ok()?;
+    Some(JemallocStats {
+        allocated_bytes: stats::allocated::read().ok()? as u64,
+        active_bytes: stats::active::read().ok()? as u64,
+        resident_bytes: stats::resident::read().ok()? as u64,
+        retained_bytes: stats::retained::read().ok()? as u64,
+        metadata_bytes: stats::metadata::read().ok()? as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::{GlobalAlloc, Layout};
+
+    use tikv_jemallocator::Jemalloc;
+
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let layout = Layout::from_size_align(64 << 20, 8).unwrap();
+        let before = stats().unwrap();
+        // SAFETY: layout has a non-zero size
+        let ptr = unsafe { Jemalloc.alloc(layout) };
+        assert!(!ptr.is_null());
+        let during = stats().unwrap();
+        // SAFETY: ptr was allocated by Jemalloc with the same layout
+        unsafe { Jemalloc.dealloc(ptr, layout) };
+        let after = stats().unwrap();
+        assert!(
+            during.allocated_bytes >= before.allocated_bytes + layout.size() as u64,
+            "{:?} {:?}",
+            before,
+            during
+        );
+        assert!(during.active_bytes >= layout.size() as u64, "{:?}", during);
+        assert!(
+            after.allocated_bytes < during.allocated_bytes,
+            "{:?} {:?}",
+            during,
+            after
+        );
+    }
+}
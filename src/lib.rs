@@ -1,30 +1,79 @@
 #![doc = include_str!("../README.md")]
+#[cfg(feature = "tracking-allocator")]
+pub mod alloc;
+pub mod build_info;
 pub mod collector;
+mod exposition;
+#[cfg(feature = "influxdb")]
+pub mod influxdb;
+#[cfg(all(feature = "jemalloc", not(target_env = "msvc")))]
+pub mod jemalloc;
+#[cfg(feature = "opentelemetry")]
+pub mod opentelemetry;
+#[cfg(any(feature = "influxdb", feature = "metrics-rs", feature = "statsd"))]
+mod periodic;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+#[cfg(feature = "prometheus-client")]
+pub mod prometheus_client;
+#[cfg(feature = "statsd")]
+pub mod statsd;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+#[cfg(feature = "tower")]
+pub mod tower;
 
+#[cfg(any(feature = "influxdb", feature = "metrics-rs", feature = "statsd"))]
+pub use periodic::PeriodicHandle;
+
+#[cfg(feature = "metrics-rs")]
+use std::io;
 #[cfg(feature = "metrics-rs")]
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, PoisonError};
+#[cfg(feature = "metrics-rs")]
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[cfg(feature = "metrics-rs")]
-use metrics::{describe_gauge, gauge, Unit};
+use metrics::{describe_gauge, describe_histogram, gauge, histogram, Label, Unit};
+
+#[cfg(feature = "metrics-rs")]
+use build_info::BuildInfo;
+#[cfg(feature = "metrics-rs")]
+use collector::{Field, MetricSet};
+#[cfg(feature = "metrics-rs")]
+use exposition::{Kind, DEFINITIONS, SCHED_INFO_HELP, SCHED_INFO_NAME, SCHED_INFO_OTEL_NAME};
 
-#[cfg(all(
-    feature = "metrics-rs",
-    not(feature = "use-gauge-on-cpu-seconds-total")
-))]
+#[cfg(feature = "metrics-rs")]
 use metrics::{counter, describe_counter};
 
 /// Metrics names
 #[cfg(feature = "metrics-rs")]
 #[derive(Debug, PartialEq, Eq)]
 struct Metrics {
-    cpu_seconds_total: Arc<str>,
-    open_fds: Arc<str>,
-    max_fds: Arc<str>,
-    virtual_memory_bytes: Arc<str>,
-    virtual_memory_max_bytes: Arc<str>,
-    resident_memory_bytes: Arc<str>,
-    start_time_seconds: Arc<str>,
-    threads: Arc<str>,
+    scheme: NamingScheme,
+    // Names of the metrics of `exposition::DEFINITIONS`, in the same order
+    definitions: Vec<Arc<str>>,
+    heap_allocations_total: Arc<str>,
+    heap_deallocations_total: Arc<str>,
+    heap_allocated_bytes_total: Arc<str>,
+    heap_deallocated_bytes_total: Arc<str>,
+    heap_arena_bytes: Arc<str>,
+    heap_in_use_bytes: Arc<str>,
+    heap_free_bytes: Arc<str>,
+    heap_mmap_bytes: Arc<str>,
+    heap_allocated_bytes: Arc<str>,
+    heap_active_bytes: Arc<str>,
+    heap_resident_bytes: Arc<str>,
+    heap_retained_bytes: Arc<str>,
+    heap_metadata_bytes: Arc<str>,
+    threads_by_state: Arc<str>,
+    sched_info: Arc<str>,
+    numa_memory_bytes: Arc<str>,
+    build_info: Arc<str>,
+    process_info: Arc<str>,
+    collector_duration_seconds: Arc<str>,
+    collector_errors_total: Arc<str>,
+    collector_last_success_timestamp_seconds: Arc<str>,
 }
 
 #[cfg(feature = "metrics-rs")]
@@ -33,14 +82,77 @@ impl Metrics {
     fn new(prefix: impl AsRef<str>) -> Self {
         let prefix = prefix.as_ref();
         Self {
-            cpu_seconds_total: format!("{prefix}process_cpu_seconds_total").into(),
-            open_fds: format!("{prefix}process_open_fds").into(),
-            max_fds: format!("{prefix}process_max_fds").into(),
-            virtual_memory_bytes: format!("{prefix}process_virtual_memory_bytes").into(),
-            virtual_memory_max_bytes: format!("{prefix}process_virtual_memory_max_bytes").into(),
-            resident_memory_bytes: format!("{prefix}process_resident_memory_bytes").into(),
-            start_time_seconds: format!("{prefix}process_start_time_seconds").into(),
-            threads: format!("{prefix}process_threads").into(),
+            scheme: NamingScheme::Prometheus,
+            definitions: DEFINITIONS
+                .iter()
+                .map(|def| format!("{prefix}{}", def.name).into())
+                .collect(),
+            heap_allocations_total: format!("{prefix}process_heap_allocations_total").into(),
+            heap_deallocations_total: format!("{prefix}process_heap_deallocations_total").into(),
+            heap_allocated_bytes_total: format!("{prefix}process_heap_allocated_bytes_total")
+                .into(),
+            heap_deallocated_bytes_total: format!("{prefix}process_heap_deallocated_bytes_total")
+                .into(),
+            heap_arena_bytes: format!("{prefix}process_heap_arena_bytes").into(),
+            heap_in_use_bytes: format!("{prefix}process_heap_in_use_bytes").into(),
+            heap_free_bytes: format!("{prefix}process_heap_free_bytes").into(),
+            heap_mmap_bytes: format!("{prefix}process_heap_mmap_bytes").into(),
+            heap_allocated_bytes: format!("{prefix}process_heap_allocated_bytes").into(),
+            heap_active_bytes: format!("{prefix}process_heap_active_bytes").into(),
+            heap_resident_bytes: format!("{prefix}process_heap_resident_bytes").into(),
+            heap_retained_bytes: format!("{prefix}process_heap_retained_bytes").into(),
+            heap_metadata_bytes: format!("{prefix}process_heap_metadata_bytes").into(),
+            build_info: format!("{prefix}process_build_info").into(),
+            process_info: format!("{prefix}process_info").into(),
+            collector_duration_seconds: format!("{prefix}process_collector_duration_seconds")
+                .into(),
+            collector_errors_total: format!("{prefix}process_collector_errors_total").into(),
+            collector_last_success_timestamp_seconds: format!(
+                "{prefix}process_collector_last_success_timestamp_seconds"
+            )
+            .into(),
+            threads_by_state: format!("{prefix}process_threads_by_state").into(),
+            sched_info: format!("{prefix}{SCHED_INFO_NAME}").into(),
+            numa_memory_bytes: format!("{prefix}process_numa_memory_bytes").into(),
+        }
+    }
+
+    // Create new Metrics with names of the OpenTelemetry semantic conventions, allocating
+    // prefixed strings for metrics names.
+    //
+    // https://opentelemetry.io/docs/specs/semconv/system/process-metrics/
+    fn new_opentelemetry(prefix: impl AsRef<str>) -> Self {
+        let prefix = prefix.as_ref();
+        Self {
+            scheme: NamingScheme::OpenTelemetry,
+            definitions: DEFINITIONS
+                .iter()
+                .map(|def| format!("{prefix}{}", def.otel_name).into())
+                .collect(),
+            heap_allocations_total: format!("{prefix}process.heap.allocations").into(),
+            heap_deallocations_total: format!("{prefix}process.heap.deallocations").into(),
+            heap_allocated_bytes_total: format!("{prefix}process.heap.allocated").into(),
+            heap_deallocated_bytes_total: format!("{prefix}process.heap.deallocated").into(),
+            heap_arena_bytes: format!("{prefix}process.heap.malloc.arena").into(),
+            heap_in_use_bytes: format!("{prefix}process.heap.malloc.in_use").into(),
+            heap_free_bytes: format!("{prefix}process.heap.malloc.free").into(),
+            heap_mmap_bytes: format!("{prefix}process.heap.malloc.mmap").into(),
+            heap_allocated_bytes: format!("{prefix}process.heap.jemalloc.allocated").into(),
+            heap_active_bytes: format!("{prefix}process.heap.jemalloc.active").into(),
+            heap_resident_bytes: format!("{prefix}process.heap.jemalloc.resident").into(),
+            heap_retained_bytes: format!("{prefix}process.heap.jemalloc.retained").into(),
+            heap_metadata_bytes: format!("{prefix}process.heap.jemalloc.metadata").into(),
+            build_info: format!("{prefix}process.build.info").into(),
+            process_info: format!("{prefix}process.info").into(),
+            collector_duration_seconds: format!("{prefix}process.collector.duration").into(),
+            collector_errors_total: format!("{prefix}process.collector.errors").into(),
+            collector_last_success_timestamp_seconds: format!(
+                "{prefix}process.collector.last_success_time"
+            )
+            .into(),
+            threads_by_state: format!("{prefix}process.thread.state.count").into(),
+            sched_info: format!("{prefix}{SCHED_INFO_OTEL_NAME}").into(),
+            numa_memory_bytes: format!("{prefix}process.numa.memory.usage").into(),
         }
     }
 }
@@ -53,6 +165,26 @@ impl Default for Metrics {
     }
 }
 
+/// Naming scheme of the metrics exported by [`Collector`].
+#[cfg(feature = "metrics-rs")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NamingScheme {
+    /// Names of the Prometheus process metrics (e.g. `process_cpu_seconds_total`).
+    #[default]
+    Prometheus,
+    /// Names of the OpenTelemetry semantic conventions (e.g. `process.cpu.time`).
+    ///
+    /// Units are not part of the names and CPU time is reported per `cpu.mode` (`user` and
+    /// `system`) rather than as a total. Metrics that are not defined by the conventions follow
+    /// the same style (e.g. `process.open_file_descriptor.limit`).
+    ///
+    /// CPU time and I/O wait time are gauges of fractional seconds, since counters of the
+    /// [metrics] crate are integers. The conventions define no attributes for the other labels, so their keys
+    /// (`state`, `node`, `policy`, `reason`, `metric` and those of the info metrics) are the same
+    /// as with [`NamingScheme::Prometheus`].
+    OpenTelemetry,
+}
+
 /// Prometheus style process metrics collector for the [metrics] crate.
 ///
 /// This is a collector which will directly export the metrics to the registered [metrics]
@@ -61,6 +193,60 @@ impl Default for Metrics {
 #[derive(Debug, Default, PartialEq, Eq, Clone)]
 pub struct Collector {
     metrics: Arc<Metrics>,
+    labels: Arc<[Label]>,
+    metric_set: MetricSet,
+    build_info: Option<Arc<BuildInfo>>,
+    process_info: Option<collector::Cmdline>,
+    self_metrics: bool,
+    series: Arc<Series>,
+}
+
+// Label sets of the series of the info and per node metrics that were set by the previous
+// collection. Their labels change over time (e.g. the scheduling policy after `chrt` or the
+// parent pid after reparenting), so the old series is set to 0 rather than left at its last
+// value next to the new one.
+#[cfg(feature = "metrics-rs")]
+#[derive(Debug, Default)]
+struct Series {
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    sched_info: LastSeries,
+    numa_memory: LastSeries,
+    process_info: LastSeries,
+}
+
+// The series are the state of the collections rather than a configuration, so they never make
+// two collectors differ.
+#[cfg(feature = "metrics-rs")]
+impl PartialEq for Series {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "metrics-rs")]
+impl Eq for Series {}
+
+#[cfg(feature = "metrics-rs")]
+#[derive(Debug, Default)]
+struct LastSeries(Mutex<Vec<Vec<Label>>>);
+
+#[cfg(feature = "metrics-rs")]
+impl LastSeries {
+    // Set the gauge of each of the series, and the gauges of the series of the previous call
+    // that are not among them to 0.
+    fn set(&self, name: &Arc<str>, series: impl IntoIterator<Item = (Vec<Label>, f64)>) {
+        let series: Vec<_> = series.into_iter().collect();
+        let mut last = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        for labels in last.drain(..) {
+            if !series.iter().any(|(l, _)| *l == labels) {
+                gauge!(Arc::clone(name), labels).set(0.0);
+            }
+        }
+        for (labels, v) in series {
+            gauge!(Arc::clone(name), labels.clone()).set(v);
+            last.push(labels);
+        }
+    }
 }
 
 #[cfg(feature = "metrics-rs")]
@@ -97,9 +283,23 @@ impl Collector {
     /// let collector = Collector::default();
     /// ```
     pub fn new(prefix: impl AsRef<str>) -> Self {
-        Self {
-            metrics: Arc::new(Metrics::new(prefix)),
-        }
+        Self::builder().prefix(prefix).build()
+    }
+
+    /// Create a [`CollectorBuilder`] to configure a new Collector instance.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use metrics_process::Collector;
+    /// # use metrics_process::collector::MetricSet;
+    /// let collector = Collector::builder()
+    ///     .prefix("my_prefix_")
+    ///     .metrics(MetricSet::CPU | MetricSet::MEMORY)
+    ///     .build();
+    /// ```
+    pub fn builder() -> CollectorBuilder {
+        CollectorBuilder::default()
     }
 
     /// Describe available metrics through `describe_counter!` and `describe_gauge!` macro of `metrics` crate.
@@ -122,56 +322,154 @@ impl Collector {
     /// ```
     pub fn describe(&self) {
         let metrics = self.metrics.as_ref();
+        let set = self.metric_set;
 
-        #[cfg(not(feature = "use-gauge-on-cpu-seconds-total"))]
-        describe_counter!(
-            Arc::clone(&metrics.cpu_seconds_total),
-            Unit::Seconds,
-            "Total user and system CPU time spent in seconds."
-        );
-        #[cfg(feature = "use-gauge-on-cpu-seconds-total")]
-        describe_gauge!(
-            Arc::clone(&metrics.cpu_seconds_total),
-            Unit::Seconds,
-            "Total user and system CPU time spent in seconds."
-        );
-        describe_gauge!(
-            Arc::clone(&metrics.open_fds),
-            Unit::Count,
-            "Number of open file descriptors."
-        );
-        describe_gauge!(
-            Arc::clone(&metrics.max_fds),
-            Unit::Count,
-            "Maximum number of open file descriptors."
-        );
-        describe_gauge!(
-            Arc::clone(&metrics.virtual_memory_bytes),
-            Unit::Bytes,
-            "Virtual memory size in bytes."
-        );
-        #[cfg(not(target_os = "windows"))]
-        describe_gauge!(
-            Arc::clone(&metrics.virtual_memory_max_bytes),
-            Unit::Bytes,
-            "Maximum amount of virtual memory available in bytes."
-        );
-        describe_gauge!(
-            Arc::clone(&metrics.resident_memory_bytes),
-            Unit::Bytes,
-            "Resident memory size in bytes."
-        );
-        describe_gauge!(
-            Arc::clone(&metrics.start_time_seconds),
-            Unit::Seconds,
-            "Start time of the process since unix epoch in seconds."
-        );
-        #[cfg(not(target_os = "windows"))]
-        describe_gauge!(
-            Arc::clone(&metrics.threads),
-            Unit::Count,
-            "Number of OS threads in the process."
-        );
+        for (def, name) in DEFINITIONS.iter().zip(metrics.definitions.iter()) {
+            if !set.contains(def.set) || !collector::is_supported(def.field) {
+                continue;
+            }
+            let help = match metrics.scheme {
+                NamingScheme::Prometheus => def.help,
+                NamingScheme::OpenTelemetry => def.otel_help.unwrap_or(def.help),
+            };
+            match (&def.kind, def.unit) {
+                (Kind::Counter, _) => describe_seconds_total(metrics.scheme, name, help),
+                (Kind::Gauge, Some("seconds")) => {
+                    describe_gauge!(Arc::clone(name), Unit::Seconds, help)
+                }
+                (Kind::Gauge, Some("bytes")) => {
+                    describe_gauge!(Arc::clone(name), Unit::Bytes, help)
+                }
+                (Kind::Gauge, _) => describe_gauge!(Arc::clone(name), Unit::Count, help),
+            }
+        }
+        #[cfg(feature = "tracking-allocator")]
+        if set.contains(MetricSet::HEAP) {
+            describe_counter!(
+                Arc::clone(&metrics.heap_allocations_total),
+                Unit::Count,
+                "Total number of heap allocations."
+            );
+            describe_counter!(
+                Arc::clone(&metrics.heap_deallocations_total),
+                Unit::Count,
+                "Total number of heap deallocations."
+            );
+            describe_counter!(
+                Arc::clone(&metrics.heap_allocated_bytes_total),
+                Unit::Bytes,
+                "Total heap allocated size in bytes."
+            );
+            describe_counter!(
+                Arc::clone(&metrics.heap_deallocated_bytes_total),
+                Unit::Bytes,
+                "Total heap deallocated size in bytes."
+            );
+        }
+        if set.contains(MetricSet::SCHED) && collector::is_supported(Field::SchedPolicy) {
+            describe_gauge!(Arc::clone(&metrics.sched_info), SCHED_INFO_HELP);
+        }
+        #[cfg(target_os = "linux")]
+        if set.contains(MetricSet::THREAD_STATES) {
+            describe_gauge!(
+                Arc::clone(&metrics.threads_by_state),
+                Unit::Count,
+                "Number of OS threads in the process by state."
+            );
+        }
+        #[cfg(target_os = "linux")]
+        if set.contains(MetricSet::NUMA_MEMORY) {
+            describe_gauge!(
+                Arc::clone(&metrics.numa_memory_bytes),
+                Unit::Bytes,
+                "Resident memory size on each NUMA node in bytes."
+            );
+        }
+        #[cfg(all(target_os = "linux", target_env = "gnu"))]
+        if set.contains(MetricSet::MALLOC_STATS) {
+            describe_gauge!(
+                Arc::clone(&metrics.heap_arena_bytes),
+                Unit::Bytes,
+                "Non-mmapped space allocated by malloc from the OS in bytes."
+            );
+            describe_gauge!(
+                Arc::clone(&metrics.heap_in_use_bytes),
+                Unit::Bytes,
+                "Space in use by malloc allocations in bytes."
+            );
+            describe_gauge!(
+                Arc::clone(&metrics.heap_free_bytes),
+                Unit::Bytes,
+                "Free space held by malloc in bytes."
+            );
+            describe_gauge!(
+                Arc::clone(&metrics.heap_mmap_bytes),
+                Unit::Bytes,
+                "Space allocated by malloc in mmapped regions in bytes."
+            );
+        }
+        #[cfg(all(feature = "jemalloc", not(target_env = "msvc")))]
+        if set.contains(MetricSet::HEAP) {
+            describe_gauge!(
+                Arc::clone(&metrics.heap_allocated_bytes),
+                Unit::Bytes,
+                "Bytes allocated by the application through jemalloc."
+            );
+            describe_gauge!(
+                Arc::clone(&metrics.heap_active_bytes),
+                Unit::Bytes,
+                "Bytes in active pages allocated by the application through jemalloc."
+            );
+            describe_gauge!(
+                Arc::clone(&metrics.heap_resident_bytes),
+                Unit::Bytes,
+                "Bytes in physically resident data pages mapped by jemalloc."
+            );
+            describe_gauge!(
+                Arc::clone(&metrics.heap_retained_bytes),
+                Unit::Bytes,
+                "Bytes in virtual memory mappings retained by jemalloc."
+            );
+            describe_gauge!(
+                Arc::clone(&metrics.heap_metadata_bytes),
+                Unit::Bytes,
+                "Bytes dedicated to jemalloc metadata."
+            );
+        }
+        #[cfg(target_os = "linux")]
+        if self.process_info.is_some() {
+            describe_gauge!(
+                Arc::clone(&metrics.process_info),
+                "Information of the process."
+            );
+        }
+        if self.self_metrics {
+            describe_histogram!(
+                Arc::clone(&metrics.collector_duration_seconds),
+                Unit::Seconds,
+                "Time spent collecting process metrics in seconds."
+            );
+            describe_counter!(
+                Arc::clone(&metrics.collector_errors_total),
+                Unit::Count,
+                "Total number of errors while collecting process metrics."
+            );
+            describe_gauge!(
+                Arc::clone(&metrics.collector_last_success_timestamp_seconds),
+                Unit::Seconds,
+                "Last time process metrics were collected without errors since unix epoch in seconds."
+            );
+        }
+        if let Some(info) = &self.build_info {
+            describe_gauge!(
+                Arc::clone(&metrics.build_info),
+                "Build information of the application."
+            );
+            // The build information never changes, so it is emitted only once here rather
+            // than on each collect().
+            let labels = with_labels(&self.labels, info.labels().map(|(k, v)| Label::new(k, v)));
+            gauge!(Arc::clone(&metrics.build_info), labels).set(1.0);
+        }
     }
 
     /// Collect metrics and record through `counter!` and `gauge!` macro of `metrics` crate.
@@ -195,35 +493,671 @@ impl Collector {
     /// ```
     pub fn collect(&self) {
         let metrics = self.metrics.as_ref();
-        let mut m = collector::collect();
-        if let Some(v) = m.cpu_seconds_total.take() {
-            #[cfg(not(feature = "use-gauge-on-cpu-seconds-total"))]
-            counter!(Arc::clone(&metrics.cpu_seconds_total)).absolute(v as u64);
-            #[cfg(feature = "use-gauge-on-cpu-seconds-total")]
-            gauge!(Arc::clone(&metrics.cpu_seconds_total)).set(v);
+        let labels = self.labels.as_ref();
+        let set = self.metric_set;
+        let started = Instant::now();
+        let (m, errors) = collector::try_collect_selected(set);
+        for (def, name) in DEFINITIONS.iter().zip(metrics.definitions.iter()) {
+            if def.field == Field::CpuSecondsTotal && metrics.scheme == NamingScheme::OpenTelemetry
+            {
+                let modes = [
+                    ("user", m.cpu_user_seconds_total),
+                    ("system", m.cpu_system_seconds_total),
+                ];
+                for (mode, v) in IntoIterator::into_iter(modes) {
+                    if let Some(v) = v {
+                        let labels = with_labels(labels, [Label::new("cpu.mode", mode)]);
+                        record_seconds_total(metrics.scheme, name, labels, v);
+                    }
+                }
+                continue;
+            }
+            let v = match (def.value)(&m) {
+                Some(v) => v,
+                None => continue,
+            };
+            match def.kind {
+                // Counters of the definitions are all totals of seconds
+                Kind::Counter => record_seconds_total(metrics.scheme, name, labels.iter(), v),
+                Kind::Gauge => gauge!(Arc::clone(name), labels.iter()).set(v),
+            }
+        }
+        #[cfg(feature = "tracking-allocator")]
+        if set.contains(MetricSet::HEAP) {
+            if let Some(s) = alloc::stats() {
+                counter!(Arc::clone(&metrics.heap_allocations_total), labels.iter())
+                    .absolute(s.allocations_total);
+                counter!(Arc::clone(&metrics.heap_deallocations_total), labels.iter())
+                    .absolute(s.deallocations_total);
+                counter!(
+                    Arc::clone(&metrics.heap_allocated_bytes_total),
+                    labels.iter()
+                )
+                .absolute(s.allocated_bytes_total);
+                counter!(
+                    Arc::clone(&metrics.heap_deallocated_bytes_total),
+                    labels.iter()
+                )
+                .absolute(s.deallocated_bytes_total);
+            }
+        }
+        #[cfg(target_os = "linux")]
+        if set.contains(MetricSet::SCHED) {
+            let policy = m.sched_policy.map(|v| {
+                let labels = with_labels(labels, [Label::new("policy", v.as_str())]);
+                (labels, 1.0)
+            });
+            self.series.sched_info.set(&metrics.sched_info, policy);
         }
-        if let Some(v) = m.open_fds.take() {
-            gauge!(Arc::clone(&metrics.open_fds)).set(v as f64);
+        if set.contains(MetricSet::THREAD_STATES) {
+            if let Some(s) = collector::collect_thread_states() {
+                for (state, v) in s.iter() {
+                    gauge!(
+                        Arc::clone(&metrics.threads_by_state),
+                        with_labels(labels, [Label::new("state", state)])
+                    )
+                    .set(v as f64);
+                }
+            }
         }
-        if let Some(v) = m.max_fds.take() {
-            gauge!(Arc::clone(&metrics.max_fds)).set(v as f64);
+        if set.contains(MetricSet::NUMA_MEMORY) {
+            let nodes = collector::collect_numa_memory()
+                .map(|n| n.resident_bytes)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(node, v)| {
+                    let labels = with_labels(labels, [Label::new("node", node.to_string())]);
+                    (labels, v as f64)
+                });
+            self.series
+                .numa_memory
+                .set(&metrics.numa_memory_bytes, nodes);
         }
-        if let Some(v) = m.virtual_memory_bytes.take() {
-            gauge!(Arc::clone(&metrics.virtual_memory_bytes)).set(v as f64);
+        if set.contains(MetricSet::MALLOC_STATS) {
+            if let Some(s) = collector::collect_malloc_stats() {
+                gauge!(Arc::clone(&metrics.heap_arena_bytes), labels.iter())
+                    .set(s.arena_bytes as f64);
+                gauge!(Arc::clone(&metrics.heap_in_use_bytes), labels.iter())
+                    .set(s.in_use_bytes as f64);
+                gauge!(Arc::clone(&metrics.heap_free_bytes), labels.iter())
+                    .set(s.free_bytes as f64);
+                gauge!(Arc::clone(&metrics.heap_mmap_bytes), labels.iter())
+                    .set(s.mmap_bytes as f64);
+            }
+        }
+        #[cfg(all(feature = "jemalloc", not(target_env = "msvc")))]
+        if set.contains(MetricSet::HEAP) {
+            if let Some(s) = jemalloc::stats() {
+                gauge!(Arc::clone(&metrics.heap_allocated_bytes), labels.iter())
+                    .set(s.allocated_bytes as f64);
+                gauge!(Arc::clone(&metrics.heap_active_bytes), labels.iter())
+                    .set(s.active_bytes as f64);
+                gauge!(Arc::clone(&metrics.heap_resident_bytes), labels.iter())
+                    .set(s.resident_bytes as f64);
+                gauge!(Arc::clone(&metrics.heap_retained_bytes), labels.iter())
+                    .set(s.retained_bytes as f64);
+                gauge!(Arc::clone(&metrics.heap_metadata_bytes), labels.iter())
+                    .set(s.metadata_bytes as f64);
+            }
+        }
+        if let Some(cmdline) = self.process_info {
+            let info = collector::collect_process_info().map(|i| {
+                let mut info_labels = with_labels(labels, [Label::new("pid", i.pid.to_string())]);
+                if let Some(v) = i.ppid {
+                    info_labels.push(Label::new("ppid", v.to_string()));
+                }
+                if let Some(v) = i.format_cmdline(cmdline) {
+                    info_labels.push(Label::new("cmdline", v));
+                }
+                if let Some(v) = i.exe {
+                    info_labels.push(Label::new("exe", v));
+                }
+                if let Some(v) = i.user {
+                    info_labels.push(Label::new("user", v));
+                }
+                if let Some(v) = i.group {
+                    info_labels.push(Label::new("group", v));
+                }
+                (info_labels, 1.0)
+            });
+            self.series.process_info.set(&metrics.process_info, info);
+        }
+        if self.self_metrics {
+            self.record_self_metrics(started.elapsed(), &errors);
+        }
+    }
+
+    // Record the collector_* metrics of a collection that took the elapsed time and failed to
+    // collect the fields in errors.
+    fn record_self_metrics(&self, elapsed: Duration, errors: &collector::Errors) {
+        let metrics = self.metrics.as_ref();
+        let labels = self.labels.as_ref();
+        histogram!(
+            Arc::clone(&metrics.collector_duration_seconds),
+            labels.iter()
+        )
+        .record(elapsed.as_secs_f64());
+        let mut success = true;
+        for (field, err) in errors.iter() {
+            if let collector::Error::Unsupported = err {
+                continue;
+            }
+            success = false;
+            counter!(
+                Arc::clone(&metrics.collector_errors_total),
+                with_labels(
+                    labels,
+                    [
+                        Label::new("metric", field.as_str()),
+                        Label::new("reason", err.reason()),
+                    ]
+                )
+            )
+            .increment(1);
+        }
+        if success {
+            if let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) {
+                gauge!(
+                    Arc::clone(&metrics.collector_last_success_timestamp_seconds),
+                    labels.iter()
+                )
+                .set(now.as_secs_f64());
+            }
+        }
+    }
+
+    /// Start a background thread named `metrics-process-collector` that calls
+    /// [`collect()`](Self::collect) immediately and then every interval.
+    ///
+    /// The thread stops when the returned handle is dropped. When a collection takes longer than
+    /// the interval, the ticks in between are skipped and counted by
+    /// [`PeriodicHandle::missed_ticks()`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval is zero.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use metrics_exporter_prometheus::PrometheusBuilder;
+    /// # use metrics_process::Collector;
+    /// // Recorder must be initialized prior to describe.
+    /// let builder = PrometheusBuilder::new();
+    /// builder.install().expect("failed to install recorder/exporter");
+    ///
+    /// let collector = Collector::default();
+    /// collector.describe();
+    /// // Collect metrics every second until the handle is dropped
+    /// let handle = collector.spawn(Duration::from_secs(1))?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn spawn(&self, interval: Duration) -> io::Result<PeriodicHandle> {
+        let collector = self.clone();
+        periodic::spawn("metrics-process-collector", interval, move || {
+            collector.collect()
+        })
+    }
+}
+
+/// A builder of [`Collector`].
+///
+/// Use [`Collector::builder()`] to create one.
+#[cfg(feature = "metrics-rs")]
+#[derive(Debug, Default, Clone)]
+pub struct CollectorBuilder {
+    prefix: String,
+    naming_scheme: NamingScheme,
+    labels: Vec<Label>,
+    metric_set: MetricSet,
+    build_info: Option<BuildInfo>,
+    process_info: Option<collector::Cmdline>,
+    self_metrics: bool,
+}
+
+#[cfg(feature = "metrics-rs")]
+impl CollectorBuilder {
+    /// Set the prefix that is prepended to metric keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use metrics_process::Collector;
+    /// let collector = Collector::builder().prefix("my_prefix_").build();
+    /// ```
+    pub fn prefix(mut self, prefix: impl AsRef<str>) -> Self {
+        self.prefix = prefix.as_ref().to_string();
+        self
+    }
+
+    /// Set the naming scheme of the metrics.
+    ///
+    /// It switches the names and units of the metrics and the `cpu.mode` label of CPU time
+    /// together; the other label keys do not change. It defaults to
+    /// [`NamingScheme::Prometheus`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use metrics_process::{Collector, NamingScheme};
+    /// // Export `process.cpu.time{cpu.mode="user"}`, `process.memory.usage`, ...
+    /// let collector = Collector::builder()
+    ///     .naming_scheme(NamingScheme::OpenTelemetry)
+    ///     .build();
+    /// ```
+    pub fn naming_scheme(mut self, scheme: NamingScheme) -> Self {
+        self.naming_scheme = scheme;
+        self
+    }
+
+    /// Set the constant labels that are attached to every metric.
+    ///
+    /// This is useful to tell apart several collectors in one process, e.g. one per logical
+    /// component. The labels are attached in both [`Collector::describe()`] (to
+    /// `process_build_info`) and [`Collector::collect()`], ahead of the labels of each metric
+    /// (e.g. `state` of `process_threads_by_state`), so they should not use the same keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use metrics::Label;
+    /// # use metrics_process::Collector;
+    /// let collector = Collector::builder()
+    ///     .labels([Label::new("component", "ingest")])
+    ///     .build();
+    /// ```
+    pub fn labels(mut self, labels: impl IntoIterator<Item = Label>) -> Self {
+        self.labels = labels.into_iter().collect();
+        self
+    }
+
+    /// Select the metrics to collect and describe.
+    ///
+    /// It defaults to [`MetricSet::default()`], which contains every metric but the expensive
+    /// [`MetricSet::THREAD_STATES`], [`MetricSet::NUMA_MEMORY`] and [`MetricSet::MALLOC_STATS`].
+    ///
+    /// [`MetricSet::THREAD_STATES`] reports the number of threads in each state (running,
+    /// sleeping, disk_sleep, stopped and zombie) as `process_threads_by_state`, walking every
+    /// thread of the process on each collection. It is only available on Linux.
+    ///
+    /// [`MetricSet::NUMA_MEMORY`] reports the resident memory of the process on each NUMA node as
+    /// `process_numa_memory_bytes`, reading the whole memory map of the process on each
+    /// collection. It is only available on Linux kernels built with NUMA support.
+    ///
+    /// [`MetricSet::MALLOC_STATS`] reports `process_heap_arena_bytes`,
+    /// `process_heap_in_use_bytes`, `process_heap_free_bytes` and `process_heap_mmap_bytes`
+    /// through `mallinfo2()`, which walks every malloc arena while holding its lock. It is only
+    /// available on Linux with glibc 2.33 or later.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use metrics_process::Collector;
+    /// # use metrics_process::collector::MetricSet;
+    /// // Only CPU time and memory
+    /// let collector = Collector::builder()
+    ///     .metrics(MetricSet::CPU | MetricSet::MEMORY)
+    ///     .build();
+    /// // Everything including thread states
+    /// let collector = Collector::builder()
+    ///     .metrics(MetricSet::default() | MetricSet::THREAD_STATES)
+    ///     .build();
+    /// ```
+    pub fn metrics(mut self, set: MetricSet) -> Self {
+        self.metric_set = set;
+        self
+    }
+
+    /// Export the build information of the application as the `process_build_info` metric.
+    ///
+    /// The metric is registered and set to 1 by [`Collector::describe()`], with the version,
+    /// revision, build profile, rustc version and target triple as labels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use metrics_process::{build_info, Collector};
+    /// let collector = Collector::builder().build_info(build_info!()).build();
+    /// ```
+    pub fn build_info(mut self, info: BuildInfo) -> Self {
+        self.build_info = Some(info);
+        self
+    }
+
+    /// Enable the `process_info` metric.
+    ///
+    /// It is an info-style metric with `pid`, `ppid`, `exe`, `cmdline`, `user` and `group`
+    /// labels that makes restarts visible. `cmdline` controls how much of the command line is
+    /// included, as it may contain secrets. It is only available on Linux.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use metrics_process::Collector;
+    /// # use metrics_process::collector::Cmdline;
+    /// let collector = Collector::builder()
+    ///     .process_info(Cmdline::Truncated(128))
+    ///     .build();
+    /// ```
+    pub fn process_info(mut self, cmdline: collector::Cmdline) -> Self {
+        self.process_info = Some(cmdline);
+        self
+    }
+
+    /// Enable or disable the metrics about the collector itself.
+    ///
+    /// It reports `process_collector_duration_seconds` (a histogram of the time spent in
+    /// [`Collector::collect()`]), `process_collector_errors_total` (labeled by `metric` and
+    /// `reason`) and `process_collector_last_success_timestamp_seconds`.
+    ///
+    /// Errors are counted for the fields of [`Metrics`](collector::Metrics) that
+    /// [`collector::try_collect`] reports, except for those that are not supported on the
+    /// platform. The other metric families (`process_threads_by_state`,
+    /// `process_numa_memory_bytes`, the glibc malloc and allocator statistics, and
+    /// `process_info`) are omitted when they are unavailable and are not counted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use metrics_process::Collector;
+    /// let collector = Collector::builder().self_metrics(true).build();
+    /// ```
+    pub fn self_metrics(mut self, enabled: bool) -> Self {
+        self.self_metrics = enabled;
+        self
+    }
+
+    /// Build a [`Collector`].
+    pub fn build(self) -> Collector {
+        Collector {
+            metrics: Arc::new(match self.naming_scheme {
+                NamingScheme::Prometheus => Metrics::new(self.prefix),
+                NamingScheme::OpenTelemetry => Metrics::new_opentelemetry(self.prefix),
+            }),
+            labels: self.labels.into(),
+            metric_set: self.metric_set,
+            build_info: self.build_info.map(Arc::new),
+            process_info: self.process_info,
+            self_metrics: self.self_metrics,
+            series: Arc::default(),
         }
-        #[cfg(not(target_os = "windows"))]
-        if let Some(v) = m.virtual_memory_max_bytes.take() {
-            gauge!(Arc::clone(&metrics.virtual_memory_max_bytes)).set(v as f64);
+    }
+}
+
+// Return the constant labels followed by the labels of a metric.
+#[cfg(feature = "metrics-rs")]
+fn with_labels(labels: &[Label], extra: impl IntoIterator<Item = Label>) -> Vec<Label> {
+    labels.iter().cloned().chain(extra).collect()
+}
+
+// Totals of seconds are gauges with the OpenTelemetry naming scheme or the
+// `use-gauge-on-cpu-seconds-total` feature to keep their fraction, since counters of the metrics
+// crate are integers. Otherwise they are counters of whole seconds.
+#[cfg(feature = "metrics-rs")]
+fn seconds_total_as_gauge(scheme: NamingScheme) -> bool {
+    scheme == NamingScheme::OpenTelemetry || cfg!(feature = "use-gauge-on-cpu-seconds-total")
+}
+
+#[cfg(feature = "metrics-rs")]
+fn describe_seconds_total(scheme: NamingScheme, name: &Arc<str>, help: &'static str) {
+    if seconds_total_as_gauge(scheme) {
+        describe_gauge!(Arc::clone(name), Unit::Seconds, help);
+    } else {
+        describe_counter!(Arc::clone(name), Unit::Seconds, help);
+    }
+}
+
+#[cfg(feature = "metrics-rs")]
+fn record_seconds_total(
+    scheme: NamingScheme,
+    name: &Arc<str>,
+    labels: impl metrics::IntoLabels,
+    v: f64,
+) {
+    if seconds_total_as_gauge(scheme) {
+        gauge!(Arc::clone(name), labels).set(v);
+    } else {
+        counter!(Arc::clone(name), labels).absolute(v as u64);
+    }
+}
+
+#[cfg(all(test, feature = "metrics-rs"))]
+mod tests {
+    use metrics_exporter_prometheus::PrometheusBuilder;
+
+    use super::*;
+
+    // Run f under a local Prometheus recorder and return the rendered output.
+    fn render(f: impl FnOnce()) -> String {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, f);
+        handle.render()
+    }
+
+    #[test]
+    fn test_self_metrics() {
+        let collector = Collector::builder().self_metrics(true).build();
+        let mut errors = collector::Errors::default();
+        errors.insert(collector::Field::MaxFds, collector::Error::Unsupported);
+        errors.insert(
+            collector::Field::OpenFds,
+            io::Error::from(io::ErrorKind::PermissionDenied).into(),
+        );
+        let text = render(|| {
+            collector.record_self_metrics(Duration::from_millis(5), &errors);
+            collector.record_self_metrics(Duration::from_millis(5), &errors);
+        });
+        assert!(
+            text.contains(
+                "process_collector_errors_total{metric=\"open_fds\",reason=\"permission_denied\"} 2\n"
+            ),
+            "{}",
+            text
+        );
+        assert!(!text.contains("metric=\"max_fds\""), "{}", text);
+        assert!(
+            text.contains("process_collector_duration_seconds_count 2\n"),
+            "{}",
+            text
+        );
+        assert!(
+            !text.contains("process_collector_last_success_timestamp_seconds"),
+            "{}",
+            text
+        );
+
+        let text = render(|| {
+            collector.record_self_metrics(Duration::from_millis(5), &collector::Errors::default())
+        });
+        assert!(!text.contains("process_collector_errors_total"), "{}", text);
+        assert!(
+            text.contains("process_collector_last_success_timestamp_seconds "),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn test_self_metrics_disabled() {
+        let text = render(|| Collector::default().collect());
+        assert!(!text.contains("process_collector_"), "{}", text);
+        let text = render(|| Collector::builder().self_metrics(true).build().collect());
+        assert!(
+            text.contains("process_collector_duration_seconds"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn test_labels() {
+        let collector = Collector::builder()
+            .labels([Label::new("component", "ingest")])
+            .metrics(MetricSet::default() | MetricSet::THREAD_STATES)
+            .build_info(build_info!())
+            .self_metrics(true)
+            .build();
+        let text = render(|| {
+            collector.describe();
+            collector.collect();
+        });
+        let samples: Vec<_> = text
+            .lines()
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .collect();
+        assert!(samples.len() > 5, "{}", text);
+        for sample in samples {
+            assert!(sample.contains("component=\"ingest\""), "{}", sample);
         }
-        if let Some(v) = m.resident_memory_bytes.take() {
-            gauge!(Arc::clone(&metrics.resident_memory_bytes)).set(v as f64);
+        assert!(
+            text.contains("process_build_info{component=\"ingest\","),
+            "{}",
+            text
+        );
+        #[cfg(target_os = "linux")]
+        assert!(
+            text.contains("process_threads_by_state{component=\"ingest\",state="),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn test_labels_empty() {
+        let text = render(|| Collector::default().collect());
+        assert!(
+            text.lines()
+                .any(|l| l.starts_with("process_resident_memory_bytes ")),
+            "{}",
+            text
+        );
+        #[cfg(target_os = "linux")]
+        assert!(text.contains("process_sched_info{policy="), "{}", text);
+        assert!(!text.contains("component="), "{}", text);
+    }
+
+    #[test]
+    fn test_describe_definitions() {
+        let collector = Collector::default();
+        let text = render(|| {
+            collector.describe();
+            collector.collect();
+        });
+        let m = collector::collect();
+        for def in DEFINITIONS {
+            if (def.value)(&m).is_some() {
+                let help = format!("# HELP {} {}\n", def.name, def.help);
+                assert!(text.contains(&help), "{}", text);
+            }
         }
-        if let Some(v) = m.start_time_seconds.take() {
-            gauge!(Arc::clone(&metrics.start_time_seconds)).set(v as f64);
+    }
+
+    #[test]
+    fn test_naming_scheme_opentelemetry() {
+        let collector = Collector::builder()
+            .naming_scheme(NamingScheme::OpenTelemetry)
+            .metrics(MetricSet::default() | MetricSet::THREAD_STATES)
+            .build();
+        let text = render(|| {
+            collector.describe();
+            collector.collect();
+        });
+        // The Prometheus exporter replaces the dots of names and label keys with underscores
+        assert!(text.contains("# TYPE process_cpu_time gauge\n"), "{}", text);
+        for mode in ["user", "system"] {
+            let sample = format!("process_cpu_time{{cpu_mode=\"{}\"}} ", mode);
+            assert!(text.contains(&sample), "{}", text);
         }
-        #[cfg(not(target_os = "windows"))]
-        if let Some(v) = m.threads.take() {
-            gauge!(Arc::clone(&metrics.threads)).set(v as f64);
+        for name in [
+            "process_memory_usage ",
+            "process_memory_virtual ",
+            "process_start_time ",
+        ] {
+            assert!(text.lines().any(|l| l.starts_with(name)), "{}", text);
         }
+        #[cfg(not(target_os = "openbsd"))]
+        assert!(
+            text.lines()
+                .any(|l| l.starts_with("process_open_file_descriptor_count ")),
+            "{}",
+            text
+        );
+        #[cfg(target_os = "linux")]
+        assert!(
+            text.contains("process_thread_count ")
+                && text.contains("process_thread_state_count{state="),
+            "{}",
+            text
+        );
+        #[cfg(target_os = "linux")]
+        assert!(
+            text.contains("# TYPE process_io_wait_time gauge\n"),
+            "{}",
+            text
+        );
+        assert!(!text.contains("process_cpu_seconds_total"), "{}", text);
+    }
+
+    #[test]
+    fn test_last_series() {
+        let series = LastSeries::default();
+        let name = Arc::from("process_sched_info");
+        let policy = |p| vec![Label::new("policy", p)];
+        let text = render(|| {
+            series.set(&name, [(policy("SCHED_OTHER"), 1.0)]);
+            series.set(&name, [(policy("SCHED_FIFO"), 1.0)]);
+        });
+        assert!(
+            text.contains("process_sched_info{policy=\"SCHED_OTHER\"} 0\n"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("process_sched_info{policy=\"SCHED_FIFO\"} 1\n"),
+            "{}",
+            text
+        );
+        let text = render(|| series.set(&name, None));
+        assert!(
+            text.contains("process_sched_info{policy=\"SCHED_FIFO\"} 0\n"),
+            "{}",
+            text
+        );
+        assert!(!text.contains("SCHED_OTHER"), "{}", text);
+    }
+
+    #[test]
+    fn test_record_seconds_total() {
+        let text = render(|| {
+            for (scheme, name) in [
+                (NamingScheme::Prometheus, "process_io_wait_seconds_total"),
+                (NamingScheme::OpenTelemetry, "process.io.wait_time"),
+            ] {
+                record_seconds_total(scheme, &Arc::from(name), Vec::<Label>::new(), 1.75);
+            }
+        });
+        #[cfg(not(feature = "use-gauge-on-cpu-seconds-total"))]
+        assert!(
+            text.contains(
+                "# TYPE process_io_wait_seconds_total counter\nprocess_io_wait_seconds_total 1\n"
+            ),
+            "{}",
+            text
+        );
+        #[cfg(feature = "use-gauge-on-cpu-seconds-total")]
+        assert!(
+            text.contains(
+                "# TYPE process_io_wait_seconds_total gauge\nprocess_io_wait_seconds_total 1.75\n"
+            ),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("# TYPE process_io_wait_time gauge\nprocess_io_wait_time 1.75\n"),
+            "{}",
+            text
+        );
     }
 }
@@ -0,0 +1,324 @@
+//! Process metrics for the [OpenTelemetry] metrics API.
+//!
+//! This module registers observable instruments on an [`opentelemetry::metrics::Meter`]. Their
+//! callbacks take a snapshot through [`collector::collect_selected`] at export time, so no
+//! [metrics] recorder has to be installed. The instruments follow the
+//! [OpenTelemetry semantic conventions] for process metrics, e.g. `process.cpu.time` with a
+//! `cpu.mode` attribute and `process.memory.usage`.
+//!
+//! [OpenTelemetry]: https://opentelemetry.io/
+//! [metrics]: https://crates.io/crates/metrics
+//! [OpenTelemetry semantic conventions]: https://opentelemetry.io/docs/specs/semconv/system/process-metrics/
+//! [`opentelemetry::metrics::Meter`]: ::opentelemetry::metrics::Meter
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ::opentelemetry::metrics::Meter;
+use ::opentelemetry::KeyValue;
+
+use crate::collector::{self, MetricSet, Metrics};
+
+// Callbacks of all instruments are invoked one after another on each export, so they share a
+// snapshot rather than each of them reading the process metrics again.
+const SNAPSHOT_TTL: Duration = Duration::from_secs(1);
+
+struct Snapshot {
+    set: MetricSet,
+    cache: Mutex<Option<(Instant, Arc<Metrics>)>>,
+}
+
+impl Snapshot {
+    fn get(&self) -> Arc<Metrics> {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((at, metrics)) = &*cache {
+            if at.elapsed() < SNAPSHOT_TTL {
+                return Arc::clone(metrics);
+            }
+        }
+        let metrics = Arc::new(collector::collect_selected(self.set));
+        *cache = Some((Instant::now(), Arc::clone(&metrics)));
+        metrics
+    }
+}
+
+/// Register observable instruments of the [`MetricSet::default()`] metrics on the meter.
+///
+/// See [`register_selected`] for details.
+///
+/// # Examples
+///
+/// ```
+/// # use opentelemetry::global;
+/// let meter = global::meter("my-service");
+/// metrics_process::opentelemetry::register(&meter);
+/// ```
+pub fn register(meter: &Meter) {
+    register_selected(meter, MetricSet::default());
+}
+
+/// Register observable instruments of the metrics in the given [`MetricSet`] on the meter.
+///
+/// Only the metrics of [`Metrics`] are registered. The instruments stay registered as long as
+/// the meter provider is alive. A snapshot taken by a callback is shared with the other
+/// instruments for one second, so each export reads the process metrics only once.
+///
+/// | Instrument                           | Kind          | Unit                |
+/// | ------------------------------------ | ------------- | ------------------- |
+/// | `process.cpu.time`                   | Counter       | `s`                 |
+/// | `process.memory.usage`               | UpDownCounter | `By`                |
+/// | `process.memory.virtual`             | UpDownCounter | `By`                |
+/// | `process.memory.virtual.limit`       | Gauge         | `By`                |
+/// | `process.heap.usage`                 | UpDownCounter | `By`                |
+/// | `process.open_file_descriptor.count` | UpDownCounter | `{file_descriptor}` |
+/// | `process.open_file_descriptor.limit` | Gauge         | `{file_descriptor}` |
+/// | `process.start_time`                 | Gauge         | `s`                 |
+/// | `process.thread.count`               | UpDownCounter | `{thread}`          |
+/// | `process.io.wait_time`               | Counter       | `s`                 |
+/// | `process.nice`                       | Gauge         | `1`                 |
+/// | `process.priority`                   | Gauge         | `1`                 |
+/// | `process.rt_priority`                | Gauge         | `1`                 |
+/// | `process.cpu.affinity`               | Gauge         | `{cpu}`             |
+///
+/// `process.cpu.time` has a `cpu.mode` attribute of `user` or `system`. Limits of 0 indicate
+/// 'unlimited'.
+pub fn register_selected(meter: &Meter, set: MetricSet) {
+    let snapshot = Arc::new(Snapshot {
+        set,
+        cache: Mutex::new(None),
+    });
+
+    if set.contains(MetricSet::CPU) {
+        let snapshot = Arc::clone(&snapshot);
+        meter
+            .f64_observable_counter("process.cpu.time")
+            .with_unit("s")
+            .with_description("Total CPU seconds broken down by different CPU modes.")
+            .with_callback(move |observer| {
+                let m = snapshot.get();
+                if let Some(v) = m.cpu_user_seconds_total {
+                    observer.observe(v, &[KeyValue::new("cpu.mode", "user")]);
+                }
+                if let Some(v) = m.cpu_system_seconds_total {
+                    observer.observe(v, &[KeyValue::new("cpu.mode", "system")]);
+                }
+            })
+            .build();
+    }
+    if set.contains(MetricSet::MEMORY) {
+        up_down_counter(
+            meter,
+            &snapshot,
+            "process.memory.usage",
+            "By",
+            "The amount of physical memory in use.",
+            |m| m.resident_memory_bytes,
+        );
+        up_down_counter(
+            meter,
+            &snapshot,
+            "process.memory.virtual",
+            "By",
+            "The amount of committed virtual memory.",
+            |m| m.virtual_memory_bytes,
+        );
+        gauge(
+            meter,
+            &snapshot,
+            "process.memory.virtual.limit",
+            "By",
+            "Maximum amount of virtual memory available.",
+            |m| m.virtual_memory_max_bytes.map(|v| v as i64),
+        );
+    }
+    if set.contains(MetricSet::HEAP) {
+        up_down_counter(
+            meter,
+            &snapshot,
+            "process.heap.usage",
+            "By",
+            "Process heap size.",
+            |m| m.heap_bytes,
+        );
+    }
+    if set.contains(MetricSet::FDS) {
+        up_down_counter(
+            meter,
+            &snapshot,
+            "process.open_file_descriptor.count",
+            "{file_descriptor}",
+            "Number of file descriptors in use by the process.",
+            |m| m.open_fds,
+        );
+        gauge(
+            meter,
+            &snapshot,
+            "process.open_file_descriptor.limit",
+            "{file_descriptor}",
+            "Maximum number of open file descriptors.",
+            |m| m.max_fds.map(|v| v as i64),
+        );
+    }
+    if set.contains(MetricSet::START_TIME) {
+        gauge(
+            meter,
+            &snapshot,
+            "process.start_time",
+            "s",
+            "Start time of the process since unix epoch.",
+            |m| m.start_time_seconds.map(|v| v as i64),
+        );
+    }
+    if set.contains(MetricSet::THREADS) {
+        up_down_counter(
+            meter,
+            &snapshot,
+            "process.thread.count",
+            "{thread}",
+            "Process threads count.",
+            |m| m.threads,
+        );
+    }
+    if set.contains(MetricSet::IO) {
+        let snapshot = Arc::clone(&snapshot);
+        meter
+            .f64_observable_counter("process.io.wait_time")
+            .with_unit("s")
+            .with_description("Total time spent waiting for block I/O.")
+            .with_callback(move |observer| {
+                if let Some(v) = snapshot.get().io_wait_seconds_total {
+                    observer.observe(v, &[]);
+                }
+            })
+            .build();
+    }
+    if set.contains(MetricSet::SCHED) {
+        gauge(
+            meter,
+            &snapshot,
+            "process.nice",
+            "1",
+            "Nice value of the process.",
+            |m| m.nice,
+        );
+        gauge(
+            meter,
+            &snapshot,
+            "process.priority",
+            "1",
+            "Kernel scheduling priority of the process.",
+            |m| m.priority,
+        );
+        gauge(
+            meter,
+            &snapshot,
+            "process.rt_priority",
+            "1",
+            "Real-time scheduling priority of the process.",
+            |m| m.rt_priority.map(|v| v as i64),
+        );
+        gauge(
+            meter,
+            &snapshot,
+            "process.cpu.affinity",
+            "{cpu}",
+            "Number of CPUs in the CPU affinity mask of the process.",
+            |m| m.cpu_affinity.map(|v| v as i64),
+        );
+    }
+}
+
+fn up_down_counter(
+    meter: &Meter,
+    snapshot: &Arc<Snapshot>,
+    name: &'static str,
+    unit: &'static str,
+    description: &'static str,
+    value: fn(&Metrics) -> Option<u64>,
+) {
+    let snapshot = Arc::clone(snapshot);
+    meter
+        .i64_observable_up_down_counter(name)
+        .with_unit(unit)
+        .with_description(description)
+        .with_callback(move |observer| {
+            if let Some(v) = value(&snapshot.get()) {
+                observer.observe(v as i64, &[]);
+            }
+        })
+        .build();
+}
+
+fn gauge(
+    meter: &Meter,
+    snapshot: &Arc<Snapshot>,
+    name: &'static str,
+    unit: &'static str,
+    description: &'static str,
+    value: fn(&Metrics) -> Option<i64>,
+) {
+    let snapshot = Arc::clone(snapshot);
+    meter
+        .i64_observable_gauge(name)
+        .with_unit(unit)
+        .with_description(description)
+        .with_callback(move |observer| {
+            if let Some(v) = value(&snapshot.get()) {
+                observer.observe(v, &[]);
+            }
+        })
+        .build();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::opentelemetry::metrics::MeterProvider as _;
+    use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+    use opentelemetry_sdk::metrics::{InMemoryMetricExporter, SdkMeterProvider};
+
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "linux",
+        target_os = "windows",
+        target_os = "freebsd"
+    ))]
+    #[test]
+    fn test_register() {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_periodic_exporter(exporter.clone())
+            .build();
+        register_selected(&provider.meter("test"), MetricSet::CPU | MetricSet::MEMORY);
+        provider.force_flush().unwrap();
+
+        let resource_metrics = exporter.get_finished_metrics().unwrap();
+        let metrics: Vec<_> = resource_metrics
+            .iter()
+            .flat_map(|rm| rm.scope_metrics())
+            .flat_map(|sm| sm.metrics())
+            .collect();
+        dbg!(&metrics);
+        let names: Vec<_> = metrics.iter().map(|m| m.name()).collect();
+        assert!(names.contains(&"process.memory.usage"));
+        assert!(!names.contains(&"process.open_file_descriptor.count"));
+
+        let cpu = metrics
+            .iter()
+            .find(|m| m.name() == "process.cpu.time")
+            .expect("process.cpu.time must be exported");
+        assert_eq!(cpu.unit(), "s");
+        match cpu.data() {
+            AggregatedMetrics::F64(MetricData::Sum(sum)) => {
+                let modes: Vec<_> = sum
+                    .data_points()
+                    .flat_map(|p| p.attributes())
+                    .map(|kv| kv.value.as_str().into_owned())
+                    .collect();
+                assert!(modes.contains(&"user".to_string()));
+                assert!(modes.contains(&"system".to_string()));
+            }
+            data => panic!("unexpected data of process.cpu.time: {:?}", data),
+        }
+    }
+}
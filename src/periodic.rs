@@ -0,0 +1,150 @@
+use std::fmt;
+use std::io;
+#[cfg(any(feature = "influxdb", feature = "statsd"))]
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// A handle of a background thread that runs a task periodically.
+///
+/// The thread is stopped when the handle is dropped or [`stop()`](Self::stop) is called. Either
+/// of them waits for the task in progress to finish.
+///
+/// Tasks that push the metrics to an agent or a receiver ignore errors of sending, since the
+/// receiver may not be running yet or may restart, and send the metrics again on the next tick.
+pub struct PeriodicHandle {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+    missed_ticks: Arc<AtomicU64>,
+}
+
+impl PeriodicHandle {
+    /// Return the number of ticks that were skipped because the task overran the interval.
+    ///
+    /// The task is never run concurrently or in a burst to catch up; it runs again on the next
+    /// tick after it finishes instead.
+    pub fn missed_ticks(&self) -> u64 {
+        self.missed_ticks.load(Ordering::Relaxed)
+    }
+
+    /// Stop the background thread and wait for it to finish.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        // The thread wakes up and exits once the sender is disconnected.
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for PeriodicHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl fmt::Debug for PeriodicHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeriodicHandle")
+            .field("thread", &self.thread.as_ref().map(|t| t.thread().name()))
+            .field("missed_ticks", &self.missed_ticks())
+            .finish()
+    }
+}
+
+// Spawn a thread with the given name that runs the task immediately and then every interval
+// until the returned handle is dropped. Ticks are skipped when the task overruns the interval.
+//
+// Panics if the interval is zero.
+pub(crate) fn spawn(
+    name: impl Into<String>,
+    interval: Duration,
+    mut task: impl FnMut() + Send + 'static,
+) -> io::Result<PeriodicHandle> {
+    assert!(!interval.is_zero(), "interval must not be zero");
+    let (stop, stopped) = mpsc::channel::<()>();
+    let missed_ticks = Arc::new(AtomicU64::new(0));
+    let thread = {
+        let missed_ticks = Arc::clone(&missed_ticks);
+        thread::Builder::new().name(name.into()).spawn(move || {
+            let mut next = Instant::now();
+            loop {
+                task();
+                next += interval;
+                let now = Instant::now();
+                while next < now {
+                    next += interval;
+                    missed_ticks.fetch_add(1, Ordering::Relaxed);
+                }
+                match stopped.recv_timeout(next - now) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => break,
+                }
+            }
+        })?
+    };
+    Ok(PeriodicHandle {
+        stop: Some(stop),
+        thread: Some(thread),
+        missed_ticks,
+    })
+}
+
+// Create a UDP socket bound to an unspecified address of the same family as the first of the
+// given addresses and connected to it, so that datagrams can be sent with `send()`.
+#[cfg(any(feature = "influxdb", feature = "statsd"))]
+pub(crate) fn connect_udp(addr: impl ToSocketAddrs) -> io::Result<UdpSocket> {
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to send to"))?;
+    let local: SocketAddr = if addr.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.connect(addr)?;
+    Ok(socket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spawn() {
+        let runs = Arc::new(AtomicU64::new(0));
+        let handle = {
+            let runs = Arc::clone(&runs);
+            spawn("test-periodic", Duration::from_millis(10), move || {
+                assert_eq!(thread::current().name(), Some("test-periodic"));
+                runs.fetch_add(1, Ordering::Relaxed);
+            })
+            .unwrap()
+        };
+        thread::sleep(Duration::from_millis(100));
+        handle.stop();
+        let stopped = runs.load(Ordering::Relaxed);
+        assert!(stopped >= 2, "{}", stopped);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(runs.load(Ordering::Relaxed), stopped);
+    }
+
+    #[test]
+    fn test_spawn_missed_ticks() {
+        let handle = spawn("test-periodic", Duration::from_millis(10), || {
+            thread::sleep(Duration::from_millis(35));
+        })
+        .unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert!(handle.missed_ticks() >= 3, "{:?}", handle);
+    }
+}
@@ -0,0 +1,179 @@
+//! Process metrics for the [prometheus] crate.
+//!
+//! This module provides [`ProcessCollector`], an implementation of
+//! [`prometheus::core::Collector`] that takes a snapshot through
+//! [`collector::collect_selected`] on each gather, so it can be registered on any
+//! [`prometheus::Registry`] without going through the [metrics] crate.
+//!
+//! [prometheus]: https://crates.io/crates/prometheus
+//! [metrics]: https://crates.io/crates/metrics
+//! [`prometheus::core::Collector`]: ::prometheus::core::Collector
+//! [`prometheus::Registry`]: ::prometheus::Registry
+
+use std::sync::Mutex;
+
+use ::prometheus::core::{Collector, Desc};
+use ::prometheus::proto::MetricFamily;
+use ::prometheus::{Counter, Gauge, GaugeVec, Opts};
+
+use crate::collector::{self, MetricSet};
+use crate::exposition::{Definition, Kind, DEFINITIONS, SCHED_INFO_HELP, SCHED_INFO_NAME};
+
+enum Instrument {
+    Counter(Counter),
+    Gauge(Gauge),
+}
+
+/// A [`prometheus::core::Collector`] of the process metrics.
+///
+/// It exports the metrics of [`Metrics`](collector::Metrics) with the same names and help
+/// strings as `Collector::describe()`, and `process_sched_info` with a `policy` label. Metrics
+/// that could not be collected are omitted from the output.
+///
+/// # Examples
+///
+/// ```
+/// # use prometheus::{Registry, TextEncoder};
+/// # use metrics_process::prometheus::ProcessCollector;
+/// let registry = Registry::new();
+/// registry
+///     .register(Box::new(ProcessCollector::new("")?))
+///     .expect("failed to register the process collector");
+/// let text = TextEncoder::new().encode_to_string(&registry.gather())?;
+/// # Ok::<(), prometheus::Error>(())
+/// ```
+///
+/// [`prometheus::core::Collector`]: ::prometheus::core::Collector
+pub struct ProcessCollector {
+    metric_set: MetricSet,
+    descs: Vec<Desc>,
+    instruments: Vec<(&'static Definition, Instrument)>,
+    sched_info: Option<GaugeVec>,
+    // Counters are set to absolute values, which is a read-modify-write, so concurrent gathers
+    // are serialized.
+    lock: Mutex<()>,
+}
+
+impl ProcessCollector {
+    /// Create a new ProcessCollector of the [`MetricSet::default()`] metrics with the provided
+    /// prefix that is prepended to metric names.
+    ///
+    /// It fails if the prefixed names are not valid Prometheus metric names.
+    pub fn new(prefix: impl AsRef<str>) -> ::prometheus::Result<Self> {
+        Self::new_selected(prefix, MetricSet::default())
+    }
+
+    /// Create a new ProcessCollector of the metrics in the given [`MetricSet`] with the provided
+    /// prefix that is prepended to metric names.
+    ///
+    /// It fails if the prefixed names are not valid Prometheus metric names.
+    pub fn new_selected(prefix: impl AsRef<str>, set: MetricSet) -> ::prometheus::Result<Self> {
+        let prefix = prefix.as_ref();
+        let mut descs = Vec::new();
+        let mut instruments = Vec::new();
+        for def in DEFINITIONS.iter().filter(|def| set.contains(def.set)) {
+            let opts = Opts::new(format!("{prefix}{}", def.name), def.help);
+            let instrument = match def.kind {
+                Kind::Counter => Instrument::Counter(Counter::with_opts(opts)?),
+                Kind::Gauge => Instrument::Gauge(Gauge::with_opts(opts)?),
+            };
+            match &instrument {
+                Instrument::Counter(c) => descs.extend(c.desc().into_iter().cloned()),
+                Instrument::Gauge(g) => descs.extend(g.desc().into_iter().cloned()),
+            }
+            instruments.push((def, instrument));
+        }
+        let sched_info = if set.contains(MetricSet::SCHED) {
+            let opts = Opts::new(format!("{prefix}{SCHED_INFO_NAME}"), SCHED_INFO_HELP);
+            let sched_info = GaugeVec::new(opts, &["policy"])?;
+            descs.extend(sched_info.desc().into_iter().cloned());
+            Some(sched_info)
+        } else {
+            None
+        };
+        Ok(Self {
+            metric_set: set,
+            descs,
+            instruments,
+            sched_info,
+            lock: Mutex::new(()),
+        })
+    }
+}
+
+impl Collector for ProcessCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.descs.iter().collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let _lock = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let m = collector::collect_selected(self.metric_set);
+        let mut mfs = Vec::with_capacity(self.descs.len());
+        for (def, instrument) in &self.instruments {
+            let v = match (def.value)(&m) {
+                Some(v) => v,
+                None => continue,
+            };
+            match instrument {
+                Instrument::Counter(c) => {
+                    // Counters only go up, so the difference from the last value is added
+                    let past = c.get();
+                    if v > past {
+                        c.inc_by(v - past);
+                    }
+                    mfs.extend(c.collect());
+                }
+                Instrument::Gauge(g) => {
+                    g.set(v);
+                    mfs.extend(g.collect());
+                }
+            }
+        }
+        if let (Some(sched_info), Some(policy)) = (&self.sched_info, m.sched_policy) {
+            sched_info.reset();
+            sched_info.with_label_values(&[policy.as_str()]).set(1.0);
+            mfs.extend(sched_info.collect());
+        }
+        mfs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::prometheus::{Registry, TextEncoder};
+
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "linux",
+        target_os = "windows",
+        target_os = "freebsd"
+    ))]
+    #[test]
+    fn test_process_collector() {
+        let registry = Registry::new();
+        let collector =
+            ProcessCollector::new_selected("my_", MetricSet::CPU | MetricSet::MEMORY).unwrap();
+        registry.register(Box::new(collector)).unwrap();
+
+        let text = TextEncoder::new()
+            .encode_to_string(&registry.gather())
+            .unwrap();
+        for expected in [
+            "# HELP my_process_cpu_seconds_total Total user and system CPU time spent in seconds.\n",
+            "# TYPE my_process_cpu_seconds_total counter\n",
+            "\nmy_process_cpu_seconds_total ",
+            "# TYPE my_process_resident_memory_bytes gauge\n",
+            "\nmy_process_resident_memory_bytes ",
+        ] {
+            assert!(text.contains(expected), "{:?} not in\n{}", expected, text);
+        }
+        assert!(!text.contains("my_process_open_fds"), "{}", text);
+    }
+
+    #[test]
+    fn test_process_collector_invalid_prefix() {
+        assert!(ProcessCollector::new("my-").is_err());
+    }
+}
@@ -0,0 +1,202 @@
+//! Process metrics for the [prometheus-client] crate.
+//!
+//! This module provides [`ProcessCollector`], an implementation of
+//! [`prometheus_client::collector::Collector`] that takes a snapshot through
+//! [`collector::collect_selected`] on each scrape, so it can be registered on an
+//! OpenMetrics-native [`prometheus_client::registry::Registry`] without going through the
+//! [metrics] crate.
+//!
+//! [prometheus-client]: https://crates.io/crates/prometheus-client
+//! [metrics]: https://crates.io/crates/metrics
+//! [`prometheus_client::collector::Collector`]: ::prometheus_client::collector::Collector
+//! [`prometheus_client::registry::Registry`]: ::prometheus_client::registry::Registry
+
+use std::fmt;
+
+use ::prometheus_client::collector::Collector;
+use ::prometheus_client::encoding::{DescriptorEncoder, EncodeMetric};
+use ::prometheus_client::metrics::counter::ConstCounter;
+use ::prometheus_client::metrics::gauge::ConstGauge;
+use ::prometheus_client::metrics::info::Info;
+use ::prometheus_client::registry::Unit;
+
+use crate::collector::{self, MetricSet};
+use crate::exposition::{Definition, Kind, DEFINITIONS, SCHED_INFO_FAMILY, SCHED_INFO_HELP};
+
+/// A [`prometheus_client::collector::Collector`] of the process metrics.
+///
+/// It encodes the metrics of [`Metrics`](collector::Metrics) with the same sample names and
+/// help strings as `Collector::describe()`, and `process_sched_info` as an info metric with a
+/// `policy` label like [`Metrics::write_openmetrics_text()`](collector::Metrics::write_openmetrics_text).
+/// Metrics measured in seconds or bytes carry a unit. Metrics that could not be collected are
+/// omitted from the output.
+///
+/// Use [`Registry::with_prefix`] to prefix the metric names.
+///
+/// # Examples
+///
+/// ```
+/// # use prometheus_client::encoding::text::encode;
+/// # use prometheus_client::registry::Registry;
+/// # use metrics_process::prometheus_client::ProcessCollector;
+/// let mut registry = Registry::default();
+/// registry.register_collector(Box::new(ProcessCollector::new()));
+///
+/// let mut text = String::new();
+/// encode(&mut text, &registry)?;
+/// # Ok::<(), std::fmt::Error>(())
+/// ```
+///
+/// [`prometheus_client::collector::Collector`]: ::prometheus_client::collector::Collector
+/// [`Registry::with_prefix`]: ::prometheus_client::registry::Registry::with_prefix
+#[derive(Debug, Default)]
+pub struct ProcessCollector {
+    metric_set: MetricSet,
+}
+
+impl ProcessCollector {
+    /// Create a new ProcessCollector of the [`MetricSet::default()`] metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new ProcessCollector of the metrics in the given [`MetricSet`].
+    pub fn new_selected(set: MetricSet) -> Self {
+        Self { metric_set: set }
+    }
+}
+
+impl Collector for ProcessCollector {
+    fn encode(&self, mut encoder: DescriptorEncoder) -> Result<(), fmt::Error> {
+        let m = collector::collect_selected(self.metric_set);
+        for def in DEFINITIONS
+            .iter()
+            .filter(|def| self.metric_set.contains(def.set))
+        {
+            let v = match (def.value)(&m) {
+                Some(v) => v,
+                None => continue,
+            };
+            let (name, unit) = split_unit(def);
+            match def.kind {
+                Kind::Counter => {
+                    let counter = ConstCounter::new(v);
+                    let metric_encoder = encoder.encode_descriptor(
+                        name,
+                        def.help,
+                        unit.as_ref(),
+                        counter.metric_type(),
+                    )?;
+                    counter.encode(metric_encoder)?;
+                }
+                Kind::Gauge => {
+                    let gauge = ConstGauge::new(v);
+                    let metric_encoder = encoder.encode_descriptor(
+                        name,
+                        def.help,
+                        unit.as_ref(),
+                        gauge.metric_type(),
+                    )?;
+                    gauge.encode(metric_encoder)?;
+                }
+            }
+        }
+        if let Some(policy) = m.sched_policy {
+            // prometheus-client appends the `_info` suffix to the sample name of info metrics
+            let info = Info::new(vec![("policy", policy.as_str())]);
+            let metric_encoder = encoder.encode_descriptor(
+                SCHED_INFO_FAMILY,
+                SCHED_INFO_HELP,
+                None,
+                info.metric_type(),
+            )?;
+            info.encode(metric_encoder)?;
+        }
+        Ok(())
+    }
+}
+
+// prometheus-client appends the unit and the `_total` suffix of counters to the names, so they
+// are stripped here to encode the same sample names as `Collector::describe()`.
+fn split_unit(def: &Definition) -> (&'static str, Option<Unit>) {
+    let name = def.family_name();
+    match def.unit {
+        Some(unit) => {
+            let name = name
+                .strip_suffix(unit)
+                .and_then(|name| name.strip_suffix('_'))
+                .unwrap_or(name);
+            let unit = match unit {
+                "seconds" => Unit::Seconds,
+                "bytes" => Unit::Bytes,
+                unit => Unit::Other(unit.to_string()),
+            };
+            (name, Some(unit))
+        }
+        None => (name, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::prometheus_client::encoding::text::encode;
+    use ::prometheus_client::registry::Registry;
+
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "linux",
+        target_os = "windows",
+        target_os = "freebsd"
+    ))]
+    #[test]
+    fn test_process_collector() {
+        let mut registry = Registry::with_prefix("my");
+        registry.register_collector(Box::new(ProcessCollector::new_selected(
+            MetricSet::CPU | MetricSet::MEMORY | MetricSet::START_TIME,
+        )));
+
+        let mut text = String::new();
+        encode(&mut text, &registry).unwrap();
+        for expected in [
+            "# HELP my_process_cpu_seconds Total user and system CPU time spent in seconds.\n",
+            "# TYPE my_process_cpu_seconds counter\n",
+            "# UNIT my_process_cpu_seconds seconds\n",
+            "\nmy_process_cpu_seconds_total ",
+            "# TYPE my_process_resident_memory_bytes gauge\n",
+            "# UNIT my_process_resident_memory_bytes bytes\n",
+            "# TYPE my_process_start_time_seconds gauge\n",
+            "# UNIT my_process_start_time_seconds seconds\n",
+        ] {
+            assert!(text.contains(expected), "{:?} not in\n{}", expected, text);
+        }
+        assert!(!text.contains("my_process_open_fds"), "{}", text);
+        assert!(text.ends_with("# EOF\n"), "{}", text);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_collector_sched_info() {
+        let mut registry = Registry::with_prefix("my");
+        registry.register_collector(Box::new(ProcessCollector::new_selected(MetricSet::SCHED)));
+
+        let mut text = String::new();
+        encode(&mut text, &registry).unwrap();
+        let mut buf = Vec::new();
+        collector::collect_selected(MetricSet::SCHED)
+            .write_openmetrics_text(&mut buf, "my_")
+            .unwrap();
+        let expected = String::from_utf8(buf).unwrap();
+        // Both encode the same info family, even if the policy changes in between
+        for line in expected.lines().filter(|l| l.contains("process_sched")) {
+            let line = line.split('{').next().unwrap();
+            assert!(text.contains(line), "{:?} not in\n{}", line, text);
+        }
+        assert!(text.contains("# TYPE my_process_sched info\n"), "{}", text);
+        assert!(
+            text.contains("\nmy_process_sched_info{policy=\""),
+            "{}",
+            text
+        );
+    }
+}
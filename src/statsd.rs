@@ -0,0 +1,430 @@
+//! Push process metrics to a [StatsD] or [DogStatsD] agent.
+//!
+//! This module provides [`StatsdEmitter`], which sends a snapshot of [`Metrics`] as StatsD lines
+//! over a UDP or Unix datagram socket, for deployments that have a local agent rather than a
+//! Prometheus server scraping them. Lines are batched into datagrams of at most
+//! [`max_packet_size`](StatsdEmitter::max_packet_size) bytes.
+//!
+//! Gauges are sent as `|g`. Counters such as `process_cpu_seconds_total` are sent as `|c` with
+//! the increase since the previous snapshot, so the first snapshot does not send them.
+//!
+//! [StatsD]: https://github.com/statsd/statsd/blob/master/docs/metric_types.md
+//! [DogStatsD]: https://docs.datadoghq.com/developers/dogstatsd/datagram_shell/
+
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::collector::{self, MetricSet, Metrics};
+use crate::exposition::{Kind, DEFINITIONS, SCHED_INFO_NAME};
+use crate::periodic::{self, PeriodicHandle};
+
+// Payload size that fits in a single Ethernet frame (1500 bytes) after the IPv6 and UDP headers.
+const UDP_MAX_PACKET_SIZE: usize = 1432;
+// DogStatsD accepts datagrams of up to 8 KiB over Unix domain sockets.
+#[cfg(unix)]
+const UNIX_MAX_PACKET_SIZE: usize = 8192;
+
+/// Line format of the datagrams.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    /// Plain StatsD lines, e.g. `process_open_fds:8|g`.
+    ///
+    /// Tags are not supported, so they and `process_sched_info` are not sent.
+    #[default]
+    Statsd,
+    /// DogStatsD lines with tags, e.g. `process_open_fds:8|g|#service:api`.
+    DogStatsd,
+}
+
+#[derive(Debug)]
+enum Socket {
+    Udp(UdpSocket),
+    #[cfg(unix)]
+    Unix(UnixDatagram),
+}
+
+impl Socket {
+    fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Socket::Udp(socket) => socket.send(buf),
+            #[cfg(unix)]
+            Socket::Unix(socket) => socket.send(buf),
+        }
+    }
+}
+
+/// An emitter that sends snapshots of [`Metrics`] to a StatsD or DogStatsD agent.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use metrics_process::statsd::{Flavor, StatsdEmitter};
+/// let handle = StatsdEmitter::udp("127.0.0.1:8125")?
+///     .flavor(Flavor::DogStatsd)
+///     .prefix("myapp.")
+///     .tags([("service", "api")])
+///     .spawn(Duration::from_secs(10))?;
+/// // The metrics are sent every 10 seconds until the handle is dropped
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct StatsdEmitter {
+    socket: Socket,
+    flavor: Flavor,
+    prefix: String,
+    tags: String,
+    max_packet_size: usize,
+    metric_set: MetricSet,
+    // Last values of the counters of DEFINITIONS, to send their increase.
+    counters: Mutex<Vec<Option<f64>>>,
+}
+
+impl StatsdEmitter {
+    /// Create a new emitter that sends datagrams to the given UDP address.
+    pub fn udp(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let socket = periodic::connect_udp(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self::new(Socket::Udp(socket), UDP_MAX_PACKET_SIZE))
+    }
+
+    /// Create a new emitter that sends datagrams to the Unix datagram socket at the given path,
+    /// e.g. `/var/run/datadog/dsd.socket`.
+    #[cfg(unix)]
+    pub fn unix(path: impl AsRef<Path>) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self::new(Socket::Unix(socket), UNIX_MAX_PACKET_SIZE))
+    }
+
+    fn new(socket: Socket, max_packet_size: usize) -> Self {
+        Self {
+            socket,
+            flavor: Flavor::default(),
+            prefix: String::new(),
+            tags: String::new(),
+            max_packet_size,
+            metric_set: MetricSet::default(),
+            counters: Mutex::new(vec![None; DEFINITIONS.len()]),
+        }
+    }
+
+    /// Set the line format. Defaults to [`Flavor::Statsd`].
+    pub fn flavor(mut self, flavor: Flavor) -> Self {
+        self.flavor = flavor;
+        self
+    }
+
+    /// Set the prefix that is prepended to metric names, e.g. `myapp.`.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Add tags that are attached to every metric.
+    ///
+    /// Tags are only sent with [`Flavor::DogStatsd`].
+    pub fn tags<K, V>(mut self, tags: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        for (key, value) in tags {
+            if !self.tags.is_empty() {
+                self.tags.push(',');
+            }
+            self.tags.push_str(key.as_ref());
+            self.tags.push(':');
+            self.tags.push_str(value.as_ref());
+        }
+        self
+    }
+
+    /// Set the maximum size of a datagram in bytes.
+    ///
+    /// Defaults to 1432 bytes for UDP, which fits in a single Ethernet frame, and to 8192 bytes
+    /// for Unix datagram sockets. A line longer than this is sent in a datagram of its own.
+    pub fn max_packet_size(mut self, size: usize) -> Self {
+        self.max_packet_size = size;
+        self
+    }
+
+    /// Select the metrics to collect in [`spawn()`](Self::spawn). Defaults to
+    /// [`MetricSet::default()`].
+    pub fn metrics(mut self, set: MetricSet) -> Self {
+        self.metric_set = set;
+        self
+    }
+
+    /// Send the metrics in the snapshot.
+    ///
+    /// Metrics that are `None` are not sent. It fails if a datagram cannot be sent, e.g. when
+    /// the agent is not listening on a Unix datagram socket. Datagrams are dropped rather than
+    /// blocking when the send buffer of the socket is full, e.g. when the agent stops reading.
+    pub fn send(&self, metrics: &Metrics) -> io::Result<()> {
+        for packet in self.packets(metrics) {
+            match self.socket.send(packet.as_bytes()) {
+                Err(e) if e.kind() != io::ErrorKind::WouldBlock => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Start a background thread named `metrics-process-statsd` that collects the selected
+    /// metrics and sends them every interval.
+    ///
+    /// Errors of sending are ignored as described in [`PeriodicHandle`]. The thread stops when
+    /// the returned handle is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval is zero.
+    pub fn spawn(self, interval: Duration) -> io::Result<PeriodicHandle> {
+        periodic::spawn("metrics-process-statsd", interval, move || {
+            let _ = self.send(&collector::collect_selected(self.metric_set));
+        })
+    }
+
+    fn packets(&self, metrics: &Metrics) -> Vec<String> {
+        let prefix = &self.prefix;
+        let mut lines = Vec::new();
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        for (def, last) in DEFINITIONS.iter().zip(counters.iter_mut()) {
+            let v = match (def.value)(metrics) {
+                Some(v) => v,
+                None => continue,
+            };
+            match def.kind {
+                Kind::Counter => {
+                    if let Some(last) = last.replace(v) {
+                        // Counters are reset when the snapshots come from different processes
+                        let delta = if v >= last { v - last } else { v };
+                        lines.push(self.line(format!("{prefix}{}:{delta}|c", def.name)));
+                    }
+                }
+                Kind::Gauge => {
+                    // Plain StatsD treats a signed value as a change of the gauge, so it is set
+                    // to zero first
+                    if v < 0.0 && self.flavor == Flavor::Statsd {
+                        lines.push(self.line(format!("{prefix}{}:0|g", def.name)));
+                    }
+                    lines.push(self.line(format!("{prefix}{}:{v}|g", def.name)));
+                }
+            }
+        }
+        drop(counters);
+        if let (Flavor::DogStatsd, Some(policy)) = (self.flavor, metrics.sched_policy) {
+            let mut line = format!("{prefix}{SCHED_INFO_NAME}:1|g|#policy:{}", policy.as_str());
+            if !self.tags.is_empty() {
+                line.push(',');
+                line.push_str(&self.tags);
+            }
+            lines.push(line);
+        }
+
+        let mut packets: Vec<String> = Vec::new();
+        for line in lines {
+            match packets.last_mut() {
+                Some(packet) if packet.len() + 1 + line.len() <= self.max_packet_size => {
+                    packet.push('\n');
+                    packet.push_str(&line);
+                }
+                _ => packets.push(line),
+            }
+        }
+        packets
+    }
+
+    fn line(&self, mut line: String) -> String {
+        if self.flavor == Flavor::DogStatsd && !self.tags.is_empty() {
+            line.push_str("|#");
+            line.push_str(&self.tags);
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collector::SchedPolicy;
+
+    fn recv_all(socket: &UdpSocket) -> Vec<String> {
+        socket
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        let mut packets = Vec::new();
+        let mut buf = [0; 65536];
+        while let Ok(n) = socket.recv(&mut buf) {
+            packets.push(String::from_utf8(buf[..n].to_vec()).unwrap());
+        }
+        packets
+    }
+
+    #[test]
+    fn test_send_udp() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let emitter = StatsdEmitter::udp(listener.local_addr().unwrap())
+            .unwrap()
+            .flavor(Flavor::DogStatsd)
+            .prefix("myapp.")
+            .tags([("service", "api"), ("env", "test")]);
+
+        let mut metrics = Metrics {
+            cpu_seconds_total: Some(1.5),
+            open_fds: Some(8),
+            nice: Some(-5),
+            sched_policy: Some(SchedPolicy::Other),
+            ..Default::default()
+        };
+        emitter.send(&metrics).unwrap();
+        assert_eq!(
+            recv_all(&listener),
+            vec![concat!(
+                "myapp.process_open_fds:8|g|#service:api,env:test\n",
+                "myapp.process_nice:-5|g|#service:api,env:test\n",
+                "myapp.process_sched_info:1|g|#policy:SCHED_OTHER,service:api,env:test",
+            )]
+        );
+
+        metrics.cpu_seconds_total = Some(2.0);
+        emitter.send(&metrics).unwrap();
+        let packets = recv_all(&listener);
+        assert_eq!(packets.len(), 1);
+        assert!(
+            packets[0].starts_with("myapp.process_cpu_seconds_total:0.5|c|#service:api,env:test\n")
+        );
+    }
+
+    #[test]
+    fn test_send_udp_statsd() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let emitter = StatsdEmitter::udp(listener.local_addr().unwrap())
+            .unwrap()
+            .tags([("service", "api")]);
+        let metrics = Metrics {
+            open_fds: Some(8),
+            nice: Some(-5),
+            sched_policy: Some(SchedPolicy::Other),
+            ..Default::default()
+        };
+        emitter.send(&metrics).unwrap();
+        assert_eq!(
+            recv_all(&listener),
+            vec!["process_open_fds:8|g\nprocess_nice:0|g\nprocess_nice:-5|g"]
+        );
+    }
+
+    #[test]
+    fn test_send_udp_batching() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let emitter = StatsdEmitter::udp(listener.local_addr().unwrap())
+            .unwrap()
+            .max_packet_size(64);
+        let metrics = Metrics {
+            open_fds: Some(8),
+            max_fds: Some(1024),
+            virtual_memory_bytes: Some(1 << 30),
+            resident_memory_bytes: Some(1 << 20),
+            threads: Some(4),
+            ..Default::default()
+        };
+        emitter.send(&metrics).unwrap();
+        let packets = recv_all(&listener);
+        assert!(packets.len() > 1);
+        assert!(packets.iter().all(|p| p.len() <= 64));
+        let lines: Vec<_> = packets.iter().flat_map(|p| p.split('\n')).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "process_open_fds:8|g",
+                "process_max_fds:1024|g",
+                "process_virtual_memory_bytes:1073741824|g",
+                "process_resident_memory_bytes:1048576|g",
+                "process_threads:4|g",
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_send_unix() {
+        let path = std::env::temp_dir().join(format!(
+            "metrics-process-statsd-{}.sock",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixDatagram::bind(&path).unwrap();
+        let emitter = StatsdEmitter::unix(&path).unwrap();
+        emitter
+            .send(&Metrics {
+                threads: Some(4),
+                ..Default::default()
+            })
+            .unwrap();
+        let mut buf = [0; 8192];
+        let n = listener.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"process_threads:4|g");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_spawn_unix_not_reading() {
+        let path = std::env::temp_dir().join(format!(
+            "metrics-process-statsd-stalled-{}.sock",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        // The agent is bound but never reads, so the queue of the socket fills up
+        let _listener = UnixDatagram::bind(&path).unwrap();
+        let emitter = StatsdEmitter::unix(&path).unwrap();
+        let metrics = Metrics {
+            threads: Some(4),
+            ..Default::default()
+        };
+        for _ in 0..10_000 {
+            emitter.send(&metrics).unwrap();
+        }
+        let handle = emitter
+            .metrics(MetricSet::THREADS)
+            .spawn(Duration::from_millis(1))
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        let (dropped, wait) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            drop(handle);
+            let _ = dropped.send(());
+        });
+        wait.recv_timeout(Duration::from_secs(5))
+            .expect("dropping the handle must not block");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
+    #[test]
+    fn test_spawn() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let handle = StatsdEmitter::udp(listener.local_addr().unwrap())
+            .unwrap()
+            .metrics(MetricSet::THREADS)
+            .spawn(Duration::from_secs(60))
+            .unwrap();
+        listener
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut buf = [0; 1432];
+        let n = listener.recv(&mut buf).unwrap();
+        let packet = std::str::from_utf8(&buf[..n]).unwrap();
+        assert!(packet.starts_with("process_threads:"), "{}", packet);
+        handle.stop();
+    }
+}
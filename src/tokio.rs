@@ -0,0 +1,251 @@
+//! Periodic collection on the [tokio] runtime.
+//!
+//! This module provides [`Collector::run()`], which collects the process metrics every interval
+//! in an async task instead of a dedicated thread. The metrics are read through
+//! [`tokio::task::spawn_blocking`] since reading `/proc` and the like may block.
+//!
+//! [tokio]: https://tokio.rs/
+//! [`tokio::task::spawn_blocking`]: ::tokio::task::spawn_blocking
+
+use std::future::{Future, IntoFuture};
+use std::pin::Pin;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ::tokio::task::{self, JoinHandle};
+use ::tokio::time::{self, Instant, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+
+use crate::Collector;
+
+impl Collector {
+    /// Create a [`Runner`] that calls [`collect()`](Self::collect) every interval on the tokio
+    /// runtime.
+    ///
+    /// Await the runner to collect in the current task, or call [`Runner::spawn()`] to collect
+    /// in a new task. Either way, it runs until its cancellation token is cancelled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval is zero.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use metrics_process::Collector;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let collector = Collector::default();
+    /// collector.describe();
+    /// // Collect metrics every 15 seconds at :00, :15, :30 and :45 until the handle is dropped
+    /// let handle = collector
+    ///     .run(Duration::from_secs(15))
+    ///     .aligned(true)
+    ///     .spawn();
+    /// # }
+    /// ```
+    pub fn run(&self, interval: Duration) -> Runner {
+        assert!(!interval.is_zero(), "interval must not be zero");
+        Runner {
+            collector: self.clone(),
+            interval,
+            aligned: false,
+            token: CancellationToken::new(),
+        }
+    }
+}
+
+/// A periodic collection created by [`Collector::run()`].
+///
+/// It is a future through [`IntoFuture`], which completes when the cancellation token is
+/// cancelled. Ticks are skipped when a collection takes longer than the interval.
+#[derive(Debug)]
+#[must_use = "a runner does nothing unless it is awaited or spawned"]
+pub struct Runner {
+    collector: Collector,
+    interval: Duration,
+    aligned: bool,
+    token: CancellationToken,
+}
+
+impl Runner {
+    /// Align the ticks to multiples of the interval since the unix epoch, e.g. at :00, :15, :30
+    /// and :45 with an interval of 15 seconds, so that processes sample at the same time.
+    ///
+    /// Otherwise, the first tick is immediate. Defaults to `false`.
+    pub fn aligned(mut self, aligned: bool) -> Self {
+        self.aligned = aligned;
+        self
+    }
+
+    /// Set the token that stops the collection when it is cancelled.
+    ///
+    /// Defaults to a new token, which is available through
+    /// [`token()`](Self::token).
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// Return the token that stops the collection when it is cancelled.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Spawn a task on the current tokio runtime that runs the collection.
+    ///
+    /// The collection is cancelled when the returned handle is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn spawn(self) -> RunHandle {
+        let token = self.token.clone();
+        let task = task::spawn(self.into_future());
+        RunHandle {
+            token,
+            task: Some(task),
+        }
+    }
+
+    async fn run(self) {
+        let Runner {
+            collector,
+            interval,
+            aligned,
+            token,
+        } = self;
+        let start = if aligned {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            Instant::now() + delay_to_next_multiple(now, interval)
+        } else {
+            Instant::now()
+        };
+        let mut ticker = time::interval_at(start, interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        token
+            .run_until_cancelled(async move {
+                loop {
+                    ticker.tick().await;
+                    let collector = collector.clone();
+                    // A panic in collect() is reported by the runtime and the next tick retries
+                    let _ = task::spawn_blocking(move || collector.collect()).await;
+                }
+            })
+            .await;
+    }
+}
+
+impl IntoFuture for Runner {
+    type Output = ();
+    type IntoFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.run())
+    }
+}
+
+/// A handle of a task spawned by [`Runner::spawn()`].
+///
+/// The task is cancelled when the handle is dropped.
+#[derive(Debug)]
+pub struct RunHandle {
+    token: CancellationToken,
+    task: Option<JoinHandle<()>>,
+}
+
+impl RunHandle {
+    /// Cancel the task.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// Return the token that cancels the task.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Cancel the task and wait for it to finish.
+    pub async fn stop(mut self) {
+        self.token.cancel();
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Drop for RunHandle {
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
+
+// `now` is the wall-clock time since unix epoch, taken by the caller so it can be fixed in tests.
+fn delay_to_next_multiple(now: Duration, interval: Duration) -> Duration {
+    let now = now.as_nanos();
+    let interval_nanos = interval.as_nanos();
+    let delay = interval_nanos - now % interval_nanos;
+    Duration::from_nanos(delay as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_to_next_multiple() {
+        let interval = Duration::from_millis(250);
+        for (now, expected) in [(1000, 250), (1100, 150), (1249, 1), (1250, 250)] {
+            assert_eq!(
+                delay_to_next_multiple(Duration::from_millis(now), interval),
+                Duration::from_millis(expected),
+                "{}",
+                now
+            );
+        }
+        assert_eq!(
+            delay_to_next_multiple(Duration::new(1, 1), Duration::from_secs(1)),
+            Duration::new(0, 999_999_999)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_cancel() {
+        let collector = Collector::default();
+        let runner = collector.run(Duration::from_millis(10));
+        let token = runner.token();
+        let task = ::tokio::spawn(runner.into_future());
+        time::sleep(Duration::from_millis(50)).await;
+        token.cancel();
+        time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("runner must stop once cancelled")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_spawn_stop() {
+        let token = CancellationToken::new();
+        let handle = Collector::default()
+            .run(Duration::from_millis(10))
+            .aligned(true)
+            .cancellation_token(token.clone())
+            .spawn();
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(!token.is_cancelled());
+        time::timeout(Duration::from_secs(5), handle.stop())
+            .await
+            .expect("task must stop once cancelled");
+        assert!(token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_spawn_drop() {
+        let handle = Collector::default().run(Duration::from_millis(10)).spawn();
+        let token = handle.token();
+        drop(handle);
+        assert!(token.is_cancelled());
+    }
+}
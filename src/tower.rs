@@ -0,0 +1,479 @@
+//! Scrape endpoints for [tower] and [axum].
+//!
+//! This module provides [`ExpositionService`], a [`Service`] that serves a snapshot of the
+//! process metrics in the Prometheus text format or the OpenMetrics text format depending on
+//! the `Accept` header of the request, and `CollectLayer`, a [`Layer`] that calls
+//! `Collector::collect()` before each request so that an existing endpoint of the [metrics]
+//! crate serves fresh process metrics (with the `metrics-rs` feature). Both of them refresh the
+//! metrics at most once per interval, so frequent scrapes do not read the process again and
+//! again.
+//!
+//! [tower]: https://crates.io/crates/tower
+//! [axum]: https://crates.io/crates/axum
+//! [metrics]: https://crates.io/crates/metrics
+//! [`Layer`]: tower_layer::Layer
+//! [`Service`]: tower_service::Service
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use ::http::header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_TYPE, VARY};
+use ::http::{Request, Response};
+use tower_service::Service;
+
+#[cfg(feature = "metrics-rs")]
+use tower_layer::Layer;
+
+use crate::collector::{self, MetricSet, Metrics};
+#[cfg(feature = "metrics-rs")]
+use crate::Collector;
+
+const DEFAULT_MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Content type of the Prometheus text exposition format.
+const TEXT_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+/// Content type of the OpenMetrics text format.
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+// A snapshot of the metrics and the time when it was taken.
+type Snapshot = Option<(Instant, Arc<Metrics>)>;
+
+/// A [`Service`] that serves the process metrics to scrapers.
+///
+/// It responds to every request with [`Metrics::write_openmetrics_text()`] when the `Accept`
+/// header prefers `application/openmetrics-text` (as Prometheus does by default) and with
+/// [`Metrics::write_prometheus_text()`] otherwise. The snapshot is taken through
+/// [`collector::collect_selected`] on a request and reused for the requests within the
+/// [`min_interval()`](Self::min_interval). Clones share the snapshot.
+///
+/// With the `tokio` feature, the metrics are read on a blocking thread of the tokio runtime
+/// through `spawn_blocking`, so the returned future does not block the executor. Otherwise, or
+/// outside of a tokio runtime, they are read when the future is polled, which blocks for as
+/// long as reading `/proc` and the like takes.
+///
+/// # Examples
+///
+/// Route the service or its [`handler()`](Self::handler) in axum.
+///
+/// ```
+/// # use axum::{routing::get, Router};
+/// # use metrics_process::tower::ExpositionService;
+/// let service = ExpositionService::new().prefix("my_app_");
+/// let app: Router = Router::new()
+///     .route_service("/metrics", service.clone())
+///     .route("/process/metrics", get(service.handler()));
+/// ```
+///
+/// [`Service`]: tower_service::Service
+/// [`Service::call`]: tower_service::Service::call
+#[derive(Debug, Clone)]
+pub struct ExpositionService {
+    prefix: Arc<str>,
+    metric_set: MetricSet,
+    min_interval: Duration,
+    snapshot: Arc<Mutex<Snapshot>>,
+}
+
+impl Default for ExpositionService {
+    fn default() -> Self {
+        Self {
+            prefix: Arc::from(""),
+            metric_set: MetricSet::default(),
+            min_interval: DEFAULT_MIN_INTERVAL,
+            snapshot: Arc::default(),
+        }
+    }
+}
+
+impl ExpositionService {
+    /// Create a new ExpositionService of the [`MetricSet::default()`] metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the prefix that is prepended to metric names.
+    pub fn prefix(mut self, prefix: impl AsRef<str>) -> Self {
+        self.prefix = Arc::from(prefix.as_ref());
+        self
+    }
+
+    /// Set the [`MetricSet`] of the metrics to serve.
+    pub fn metrics(mut self, set: MetricSet) -> Self {
+        self.metric_set = set;
+        self
+    }
+
+    /// Set the minimum interval between two snapshots. Defaults to 1 second.
+    ///
+    /// Requests within the interval are served from the previous snapshot.
+    pub fn min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    /// Return an [axum] handler that serves the metrics like the service itself.
+    ///
+    /// [axum]: https://crates.io/crates/axum
+    pub fn handler(
+        &self,
+    ) -> impl Fn(HeaderMap) -> Pin<Box<dyn Future<Output = Response<String>> + Send + 'static>>
+           + Clone
+           + Send
+           + Sync
+           + 'static {
+        let service = self.clone();
+        move |headers: HeaderMap| {
+            let format = Format::negotiate(headers.get_all(ACCEPT));
+            Box::pin(service.clone().respond_nonblocking(format))
+        }
+    }
+
+    /// Render the response to a request with the given headers.
+    ///
+    /// This reads the metrics on the current thread when the snapshot is outdated.
+    pub fn render(&self, headers: &HeaderMap) -> Response<String> {
+        let format = Format::negotiate(headers.get_all(ACCEPT));
+        self.respond(format, &self.snapshot())
+    }
+
+    async fn respond_nonblocking(self, format: Format) -> Response<String> {
+        let metrics = match self.cached() {
+            Some(metrics) => metrics,
+            None => self.collect_nonblocking().await,
+        };
+        self.respond(format, &metrics)
+    }
+
+    fn respond(&self, format: Format, metrics: &Metrics) -> Response<String> {
+        let mut buf = Vec::new();
+        match format {
+            Format::Text => metrics.write_prometheus_text(&mut buf, &*self.prefix),
+            Format::OpenMetrics => metrics.write_openmetrics_text(&mut buf, &*self.prefix),
+        }
+        .expect("writing to a Vec never fails");
+        let mut response = Response::new(String::from_utf8_lossy(&buf).into_owned());
+        let headers = response.headers_mut();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static(format.content_type()),
+        );
+        headers.insert(VARY, HeaderValue::from_static("accept"));
+        response
+    }
+
+    fn snapshot(&self) -> Arc<Metrics> {
+        self.cached().unwrap_or_else(|| self.collect())
+    }
+
+    // Return the snapshot if it was taken within the minimum interval.
+    fn cached(&self) -> Option<Arc<Metrics>> {
+        let snapshot = self.snapshot.lock().unwrap_or_else(PoisonError::into_inner);
+        match &*snapshot {
+            Some((at, metrics)) if at.elapsed() < self.min_interval => Some(Arc::clone(metrics)),
+            _ => None,
+        }
+    }
+
+    // Take a new snapshot. The lock is not held while collecting, so that a slow collection
+    // never blocks requests that are served from the previous snapshot.
+    fn collect(&self) -> Arc<Metrics> {
+        let metrics = Arc::new(collector::collect_selected(self.metric_set));
+        let mut snapshot = self.snapshot.lock().unwrap_or_else(PoisonError::into_inner);
+        *snapshot = Some((Instant::now(), Arc::clone(&metrics)));
+        metrics
+    }
+
+    // Take a new snapshot on a blocking thread of the tokio runtime if any.
+    async fn collect_nonblocking(&self) -> Arc<Metrics> {
+        #[cfg(feature = "tokio")]
+        if let Ok(handle) = ::tokio::runtime::Handle::try_current() {
+            let service = self.clone();
+            match handle.spawn_blocking(move || service.collect()).await {
+                Ok(metrics) => return metrics,
+                Err(err) => std::panic::resume_unwind(err.into_panic()),
+            }
+        }
+        self.collect()
+    }
+}
+
+impl<B> Service<Request<B>> for ExpositionService {
+    type Response = Response<String>;
+    type Error = Infallible;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let format = Format::negotiate(req.headers().get_all(ACCEPT));
+        let response = self.clone().respond_nonblocking(format);
+        Box::pin(async move { Ok(response.await) })
+    }
+}
+
+/// A [`Layer`] that calls [`Collector::collect()`] before each request to the inner service.
+///
+/// Wrap the scrape endpoint of a [metrics] exporter to refresh the process metrics at scrape
+/// time. The collection runs at most once per [`min_interval()`](Self::min_interval), and
+/// services created by the same layer share the interval.
+///
+/// The collection runs in [`Service::call`] before the inner service is called, so it blocks
+/// the executor for as long as reading `/proc` and the like takes. Collect in the background
+/// with [`Collector::spawn()`] instead where that matters.
+///
+/// # Examples
+///
+/// ```
+/// # use axum::{routing::get, Router};
+/// # use metrics_exporter_prometheus::PrometheusBuilder;
+/// # use metrics_process::Collector;
+/// # use metrics_process::tower::CollectLayer;
+/// let handle = PrometheusBuilder::new().build_recorder().handle();
+/// let collector = Collector::default();
+/// collector.describe();
+///
+/// let app: Router = Router::new().route(
+///     "/metrics",
+///     get(move || std::future::ready(handle.render())).layer(CollectLayer::new(collector)),
+/// );
+/// ```
+///
+/// [`Layer`]: tower_layer::Layer
+/// [`Service::call`]: tower_service::Service::call
+#[cfg(feature = "metrics-rs")]
+#[derive(Debug, Clone)]
+pub struct CollectLayer {
+    collector: Collector,
+    min_interval: Duration,
+    last: Arc<Mutex<Option<Instant>>>,
+}
+
+#[cfg(feature = "metrics-rs")]
+impl CollectLayer {
+    /// Create a new CollectLayer that collects through the given collector.
+    pub fn new(collector: Collector) -> Self {
+        Self {
+            collector,
+            min_interval: DEFAULT_MIN_INTERVAL,
+            last: Arc::default(),
+        }
+    }
+
+    /// Set the minimum interval between two collections. Defaults to 1 second.
+    pub fn min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+}
+
+#[cfg(feature = "metrics-rs")]
+impl<S> Layer<S> for CollectLayer {
+    type Service = Collect<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Collect {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// A [`Service`] created by [`CollectLayer`].
+///
+/// [`Service`]: tower_service::Service
+#[cfg(feature = "metrics-rs")]
+#[derive(Debug, Clone)]
+pub struct Collect<S> {
+    inner: S,
+    layer: CollectLayer,
+}
+
+#[cfg(feature = "metrics-rs")]
+impl<S, R> Service<R> for Collect<S>
+where
+    S: Service<R>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let layer = &self.layer;
+        let due = {
+            let mut last = layer.last.lock().unwrap_or_else(PoisonError::into_inner);
+            #[allow(clippy::unnecessary_map_or)] // Option::is_none_or requires Rust 1.82
+            let due = last.map_or(true, |at| at.elapsed() >= layer.min_interval);
+            if due {
+                // Claim the interval before collecting, so that concurrent requests neither
+                // collect again nor wait for the lock during the collection.
+                *last = Some(Instant::now());
+            }
+            due
+        };
+        if due {
+            layer.collector.collect();
+        }
+        self.inner.call(req)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    OpenMetrics,
+}
+
+impl Format {
+    // Choose OpenMetrics when the Accept header weighs it at least as much as the text format,
+    // and the text format otherwise, even when neither of them is acceptable.
+    fn negotiate<'a>(accept: impl IntoIterator<Item = &'a HeaderValue>) -> Self {
+        let mut openmetrics = 0.0_f32;
+        let mut text = 0.0_f32;
+        for value in accept {
+            let value = match value.to_str() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            for range in value.split(',') {
+                let mut params = range.split(';');
+                let media_type = params.next().unwrap_or_default().trim();
+                let q = params
+                    .filter_map(|param| param.split_once('='))
+                    .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+                    .map_or(Some(1.0), |(_, q)| q.trim().parse::<f32>().ok())
+                    .unwrap_or(0.0);
+                if media_type.eq_ignore_ascii_case("application/openmetrics-text") {
+                    openmetrics = openmetrics.max(q);
+                } else if ["text/plain", "text/*", "*/*"]
+                    .iter()
+                    .any(|t| media_type.eq_ignore_ascii_case(t))
+                {
+                    text = text.max(q);
+                }
+            }
+        }
+        if openmetrics > 0.0 && openmetrics >= text {
+            Format::OpenMetrics
+        } else {
+            Format::Text
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Text => TEXT_CONTENT_TYPE,
+            Format::OpenMetrics => OPENMETRICS_CONTENT_TYPE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+
+    use super::*;
+
+    fn negotiate(accept: &[&'static str]) -> Format {
+        let values: Vec<_> = accept.iter().map(|v| HeaderValue::from_static(v)).collect();
+        Format::negotiate(&values)
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(&[]), Format::Text);
+        assert_eq!(negotiate(&["text/plain"]), Format::Text);
+        assert_eq!(negotiate(&["application/json"]), Format::Text);
+        assert_eq!(
+            negotiate(&["application/openmetrics-text; version=1.0.0"]),
+            Format::OpenMetrics
+        );
+        // The default Accept header of Prometheus
+        assert_eq!(
+            negotiate(&["application/openmetrics-text;version=1.0.0;q=0.5,application/openmetrics-text;version=0.0.1;q=0.4,text/plain;version=0.0.4;q=0.3,*/*;q=0.2"]),
+            Format::OpenMetrics
+        );
+        assert_eq!(
+            negotiate(&["application/openmetrics-text;q=0.2", "text/plain;q=0.8"]),
+            Format::Text
+        );
+        assert_eq!(
+            negotiate(&["Application/OpenMetrics-Text;q=0, */*"]),
+            Format::Text
+        );
+    }
+
+    #[tokio::test]
+    async fn test_call() {
+        let mut service = ExpositionService::new().prefix("test_");
+        poll_fn(|cx| Service::<Request<()>>::poll_ready(&mut service, cx))
+            .await
+            .unwrap();
+        let response = service.call(Request::new(())).await.unwrap();
+        assert_eq!(response.headers()[CONTENT_TYPE], TEXT_CONTENT_TYPE);
+        assert_eq!(response.headers()[VARY], "accept");
+        assert!(response.body().contains("# TYPE test_process_"));
+        assert!(!response.body().contains("# EOF"));
+
+        let request = Request::builder()
+            .header(ACCEPT, "application/openmetrics-text")
+            .body(())
+            .unwrap();
+        let response = service.call(request).await.unwrap();
+        assert_eq!(response.headers()[CONTENT_TYPE], OPENMETRICS_CONTENT_TYPE);
+        assert!(response.body().ends_with("# EOF\n"));
+    }
+
+    #[tokio::test]
+    async fn test_handler() {
+        let handler = ExpositionService::new().prefix("test_").handler();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static("application/openmetrics-text"),
+        );
+        let response = handler(headers).await;
+        assert_eq!(response.headers()[CONTENT_TYPE], OPENMETRICS_CONTENT_TYPE);
+        assert!(response.body().contains("# TYPE test_process_"));
+    }
+
+    #[test]
+    fn test_min_interval() {
+        let service = ExpositionService::new();
+        assert!(Arc::ptr_eq(
+            &service.snapshot(),
+            &service.clone().snapshot()
+        ));
+
+        let service = ExpositionService::new().min_interval(Duration::ZERO);
+        assert!(!Arc::ptr_eq(&service.snapshot(), &service.snapshot()));
+    }
+
+    #[cfg(feature = "metrics-rs")]
+    #[tokio::test]
+    async fn test_collect_layer() {
+        let layer = CollectLayer::new(Collector::default());
+        let mut service = layer.layer(ExpositionService::new());
+        poll_fn(|cx| Service::<Request<()>>::poll_ready(&mut service, cx))
+            .await
+            .unwrap();
+        service.call(Request::new(())).await.unwrap();
+        let collected = layer
+            .last
+            .lock()
+            .unwrap()
+            .expect("must collect on a request");
+        service.call(Request::new(())).await.unwrap();
+        assert_eq!(*layer.last.lock().unwrap(), Some(collected));
+    }
+}